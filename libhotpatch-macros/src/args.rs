@@ -1,34 +1,249 @@
 use syn::{
-    Error, Ident, Result,
+    Error, Ident, LitStr, Path, Result, Token,
     parse::{Parse, ParseStream},
+    punctuated::Punctuated,
 };
 
+#[derive(Default)]
 pub struct Args {
     pub is_checked: bool,
+    pub codec: Codec,
+    pub is_archived: bool,
+    pub fallback_on_panic: Option<FallbackScope>,
+    pub crash_guard: bool,
+    pub shadow: bool,
+    pub on_mismatch: OnMismatch,
+}
+
+/// The serialization format of a `checked` function.
+#[derive(Default)]
+pub enum Codec {
+    /// Selected at runtime by `LIBHOTPATCH_CODEC`.
+    #[default]
+    Configured,
+    /// One of the codecs of `libhotpatch::codec`, by name.
+    Named(&'static str),
+    /// A user type implementing `HotpatchCodec`.
+    Path(String, Path),
+}
+
+impl Codec {
+    /// The name, type and whether the cargo feature of every named codec is enabled.
+    const NAMED: [(&'static str, &'static str, bool); 4] = [
+        ("messagepack", "MessagePack", true),
+        ("bincode", "Bincode", cfg!(feature = "bincode")),
+        ("postcard", "Postcard", cfg!(feature = "postcard")),
+        ("json", "Json", cfg!(feature = "json")),
+    ];
+
+    pub fn path(&self) -> Path {
+        match self {
+            Codec::Configured => syn::parse_quote!(libhotpatch::codec::Configured),
+            Codec::Named(name) => {
+                let (_, ty, _) = Self::NAMED.iter().find(|(n, ..)| n == name).unwrap();
+                let ty = Ident::new(ty, proc_macro2::Span::call_site());
+                syn::parse_quote!(libhotpatch::codec::#ty)
+            }
+            Codec::Path(_, path) => path.clone(),
+        }
+    }
+
+    fn name(&self) -> Option<&str> {
+        match self {
+            Codec::Configured => None,
+            Codec::Named(name) => Some(name),
+            Codec::Path(name, _) => Some(name),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum FallbackScope {
+    Function,
+    Generation,
+}
+
+#[derive(Clone, Copy, Default)]
+pub enum OnMismatch {
+    #[default]
+    Panic,
+    Fallback,
+    Default,
+}
+
+impl Args {
+    /// Whether the function slot points to a `fallback_call` shim instead of the function.
+    pub fn uses_fallback_call(&self) -> bool {
+        self.fallback_on_panic.is_some() || self.crash_guard
+    }
+
+    /// Describes how the function slot is called, which differs between some attributes.
+    pub fn dispatch_abi(&self) -> String {
+        let mut abi = vec![];
+
+        if self.is_checked {
+            abi.push(match self.codec.name() {
+                None => "checked".to_string(),
+                Some(codec) => format!("checked={codec}"),
+            });
+        }
+
+        if self.is_archived {
+            abi.push("archived".to_string());
+        }
+
+        if self.uses_fallback_call() {
+            abi.push("fallback_call".to_string());
+        }
+
+        abi.join(",")
+    }
+}
+
+struct Arg {
+    name: Ident,
+    value: Option<LitStr>,
 }
 
 impl Parse for Args {
     fn parse(input: ParseStream) -> Result<Self> {
-        if !input.peek(Ident) {
-            return Ok(Args { is_checked: false });
-        }
+        let mut args = Args::default();
+        let mut on_mismatch = None;
+
+        for Arg { name, value } in Punctuated::<Arg, Token![,]>::parse_terminated(input)? {
+            if name == "checked" {
+                if !cfg!(feature = "checked") {
+                    return Err(Error::new_spanned(&name, "feature \"checked\" is disabled"));
+                }
+
+                args.is_checked = true;
+                args.codec = match value {
+                    None => Codec::Configured,
+                    Some(value) => match Codec::NAMED.iter().find(|(n, ..)| *n == value.value()) {
+                        Some((name, _, false)) => {
+                            return Err(Error::new_spanned(
+                                &value,
+                                format!("feature \"{name}\" is disabled"),
+                            ));
+                        }
+                        Some((name, _, true)) => Codec::Named(name),
+                        None => Codec::Path(value.value(), value.parse::<Path>().map_err(|_| {
+                            Error::new_spanned(
+                                &value,
+                                "unsupported codec, is not one of: \"messagepack\", \"bincode\", \"postcard\", \"json\", or a path to a `HotpatchCodec`",
+                            )
+                        })?),
+                    },
+                };
+            } else if name == "archived" {
+                if let Some(value) = value {
+                    return Err(Error::new_spanned(
+                        value,
+                        "\"archived\" does not take a value",
+                    ));
+                }
+
+                if !cfg!(feature = "archived") {
+                    return Err(Error::new_spanned(
+                        &name,
+                        "feature \"archived\" is disabled",
+                    ));
+                }
+
+                args.is_archived = true;
+            } else if name == "fallback_on_panic" {
+                args.fallback_on_panic = Some(match value {
+                    None => FallbackScope::Function,
+                    Some(value) if value.value() == "function" => FallbackScope::Function,
+                    Some(value) if value.value() == "generation" => FallbackScope::Generation,
+                    Some(value) => {
+                        return Err(Error::new_spanned(
+                            value,
+                            "unsupported value, is not one of: \"function\", \"generation\"",
+                        ));
+                    }
+                });
+            } else if name == "crash_guard" {
+                if let Some(value) = value {
+                    return Err(Error::new_spanned(
+                        value,
+                        "\"crash_guard\" does not take a value",
+                    ));
+                }
+
+                args.crash_guard = true;
+            } else if name == "shadow" {
+                if let Some(value) = value {
+                    return Err(Error::new_spanned(
+                        value,
+                        "\"shadow\" does not take a value",
+                    ));
+                }
 
-        let checked = input.parse::<Ident>()?;
+                args.shadow = true;
+            } else if name == "on_mismatch" {
+                args.on_mismatch = match value {
+                    Some(value) if value.value() == "panic" => OnMismatch::Panic,
+                    Some(value) if value.value() == "fallback" => OnMismatch::Fallback,
+                    Some(value) if value.value() == "default" => OnMismatch::Default,
+                    Some(value) => {
+                        return Err(Error::new_spanned(
+                            value,
+                            "unsupported value, is not one of: \"panic\", \"fallback\", \"default\"",
+                        ));
+                    }
+                    None => {
+                        return Err(Error::new_spanned(
+                            &name,
+                            "\"on_mismatch\" requires a value",
+                        ));
+                    }
+                };
 
-        if checked.to_string() != "checked" {
+                on_mismatch = Some(name);
+            } else {
+                return Err(Error::new_spanned(
+                    &name,
+                    "unsupported attribute, is not one of: \"checked\", \"archived\", \"fallback_on_panic\", \"crash_guard\", \"shadow\", \"on_mismatch\"",
+                ));
+            }
+        }
+
+        if let Some(name) = on_mismatch
+            && !args.is_checked
+            && !args.is_archived
+        {
             return Err(Error::new_spanned(
-                &checked,
-                "unsupported attribute, is not one of: \"checked\"",
+                name,
+                "\"on_mismatch\" requires \"checked\" or \"archived\"",
             ));
         }
 
-        if !cfg!(feature = "checked") {
-            return Err(Error::new_spanned(
-                &checked,
-                "feature \"checked\" is disabled",
+        if args.is_checked && args.is_archived {
+            return Err(input.error("\"checked\" can not be combined with \"archived\""));
+        }
+
+        if args.shadow && args.uses_fallback_call() {
+            return Err(input.error(
+                "\"shadow\" can not be combined with \"fallback_on_panic\" or \"crash_guard\"",
             ));
         }
 
-        Ok(Args { is_checked: true })
+        Ok(args)
+    }
+}
+
+impl Parse for Arg {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let name = input.parse::<Ident>()?;
+
+        let value = if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            Some(input.parse::<LitStr>()?)
+        } else {
+            None
+        };
+
+        Ok(Arg { name, value })
     }
 }
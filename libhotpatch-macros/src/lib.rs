@@ -1,11 +1,15 @@
 use proc_macro2::{Span, TokenStream};
-use quote::{ToTokens, quote};
+use quote::{ToTokens, format_ident, quote};
 use syn::{
-    Abi, FnArg, ImplItemFn, LitByteStr, LitStr, Pat, PatWild, Token, parse_macro_input,
-    parse_quote, token::Extern,
+    Abi, Error, FnArg, Generics, Ident, ImplItemFn, ItemFn, ItemImpl, ItemStatic, LitByteStr,
+    LitStr, Pat, Path, ReturnType, StaticMutability, Token, Type, parse_macro_input, parse_quote,
+    punctuated::Punctuated, token::Extern,
 };
 
-use crate::{args::Args, hotpatch_fn::HotpatchFn};
+use crate::{
+    args::{Args, FallbackScope, OnMismatch},
+    hotpatch_fn::HotpatchFn,
+};
 
 mod args;
 mod hotpatch_fn;
@@ -15,18 +19,167 @@ pub fn hotpatch(
     args: proc_macro::TokenStream,
     input: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-    let Args { is_checked } = parse_macro_input!(args as Args);
+    if let Ok(item) = syn::parse::<ItemStatic>(input.clone()) {
+        return hotpatch_static(args.into(), item).into();
+    }
+
+    if let Ok(item) = syn::parse::<ItemImpl>(input.clone()) {
+        return hotpatch_impl(args.into(), item).into();
+    }
+
+    let args = parse_macro_input!(args as Args);
     let hotpatch_fn = parse_macro_input!(input as HotpatchFn);
 
-    if is_checked {
-        hotpatch_checked(hotpatch_fn)
+    if args.is_checked {
+        hotpatch_checked(hotpatch_fn, &args)
+    } else if args.is_archived {
+        hotpatch_archived(hotpatch_fn, &args)
     } else {
-        hotpatch_unchecked(hotpatch_fn)
+        hotpatch_unchecked(hotpatch_fn, &args)
+    }
+    .into()
+}
+
+/// Generates a `#[hotpatch]` static, whose value is read through a slot of the function
+/// table.
+fn hotpatch_static(args: TokenStream, item: ItemStatic) -> TokenStream {
+    if !args.is_empty() {
+        return Error::new_spanned(args, "a hot-patch static does not take arguments")
+            .to_compile_error();
+    }
+
+    let ItemStatic {
+        attrs,
+        vis,
+        mutability,
+        ident,
+        ty,
+        expr,
+        ..
+    } = item;
+
+    if let StaticMutability::Mut(mutability) = mutability {
+        return Error::new_spanned(mutability, "a hot-patch static cannot be `mut`")
+            .to_compile_error();
+    }
+
+    quote! {
+        #(#attrs)*
+        #vis static #ident: libhotpatch::HotConst<#ty> = {
+            static VALUE: #ty = #expr;
+            // Statics of another type must never be matched.
+            fn type_of() -> (u128, &'static str) {
+                let name = ::std::concat!(::std::module_path!(), "::", ::std::stringify!(#ident));
+                let mut hasher = libhotpatch::Xxh3::new();
+                ::std::hash::Hash::hash(b"static", &mut hasher);
+                ::std::hash::Hash::hash(::std::any::type_name::<#ty>(), &mut hasher);
+                ::std::hash::Hash::hash(&::std::mem::size_of::<#ty>(), &mut hasher);
+                ::std::hash::Hash::hash(&::std::mem::align_of::<#ty>(), &mut hasher);
+                ::std::hash::Hash::hash(name.as_bytes(), &mut hasher);
+                (hasher.digest128(), name)
+            }
+            #[libhotpatch::distributed_slice(libhotpatch::HOTPATCH_FN)]
+            #[linkme(crate = libhotpatch::linkme)]
+            static HOTPATCH_FN: libhotpatch::HotpatchSlot =
+                libhotpatch::HotpatchSlot::new(&raw const VALUE as *mut (), type_of);
+            libhotpatch::HotConst::new(&HOTPATCH_FN)
+        };
+    }
+}
+
+/// Generates a `#[hotpatch]` trait impl, whose vtable is resolved through a slot of the
+/// function table by `HotDyn`.
+fn hotpatch_impl(args: TokenStream, item: ItemImpl) -> TokenStream {
+    if !args.is_empty() {
+        return Error::new_spanned(args, "a hot-patch impl does not take arguments")
+            .to_compile_error();
+    }
+
+    let Some((None, trait_path, _)) = &item.trait_ else {
+        return Error::new_spanned(&item.self_ty, "a hot-patch impl must implement a trait")
+            .to_compile_error();
+    };
+
+    if !item.generics.params.is_empty() {
+        return Error::new_spanned(&item.generics, "a hot-patch impl cannot be generic")
+            .to_compile_error();
+    }
+
+    let self_ty = &item.self_ty;
+
+    quote! {
+        #item
+        const _: () = {
+            // Vtables of types with another layout must never be matched.
+            fn type_of() -> (u128, &'static str) {
+                let name = ::std::concat!(
+                    ::std::module_path!(),
+                    "::<",
+                    ::std::stringify!(#self_ty),
+                    " as ",
+                    ::std::stringify!(#trait_path),
+                    ">",
+                );
+                let mut hasher = libhotpatch::Xxh3::new();
+                ::std::hash::Hash::hash(b"impl", &mut hasher);
+                ::std::hash::Hash::hash(::std::any::type_name::<dyn #trait_path>(), &mut hasher);
+                ::std::hash::Hash::hash(::std::any::type_name::<#self_ty>(), &mut hasher);
+                ::std::hash::Hash::hash(&::std::mem::size_of::<#self_ty>(), &mut hasher);
+                ::std::hash::Hash::hash(&::std::mem::align_of::<#self_ty>(), &mut hasher);
+                ::std::hash::Hash::hash(name.as_bytes(), &mut hasher);
+                (hasher.digest128(), name)
+            }
+            fn cast(data: *mut ()) -> *mut dyn #trait_path {
+                data.cast::<#self_ty>() as *mut dyn #trait_path
+            }
+            #[libhotpatch::distributed_slice(libhotpatch::HOTPATCH_FN)]
+            #[linkme(crate = libhotpatch::linkme)]
+            static HOTPATCH_FN: libhotpatch::HotpatchSlot = libhotpatch::HotpatchSlot::new(
+                cast as fn(*mut ()) -> *mut dyn #trait_path as *mut (),
+                type_of,
+            );
+            unsafe impl libhotpatch::HotImpl<dyn #trait_path> for #self_ty {
+                fn slot() -> &'static libhotpatch::HotpatchSlot {
+                    &HOTPATCH_FN
+                }
+            }
+        };
+    }
+}
+
+#[proc_macro_attribute]
+pub fn canary(
+    args: proc_macro::TokenStream,
+    input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let target = parse_macro_input!(args as Path);
+    let check = parse_macro_input!(input as ItemFn);
+
+    let check_fn = &check.sig.ident;
+
+    quote! {
+        #check
+        const _: () = {
+            // Panics must be caught in the generation that raised them.
+            extern "C" fn canary_call() -> bool {
+                ::std::panic::catch_unwind(|| #check_fn()).unwrap_or(false)
+            }
+            fn target_name() -> &'static str {
+                ::std::any::type_name_of_val(&#target)
+            }
+            #[libhotpatch::distributed_slice(libhotpatch::HOTPATCH_CANARY)]
+            #[linkme(crate = libhotpatch::linkme)]
+            static HOTPATCH_CANARY: libhotpatch::Canary =
+                libhotpatch::Canary::new(canary_call, target_name);
+        };
     }
     .into()
 }
 
-fn hotpatch_checked(HotpatchFn { inner, outer }: HotpatchFn) -> TokenStream {
+fn hotpatch_checked(HotpatchFn { inner, outer }: HotpatchFn, hotpatch_args: &Args) -> TokenStream {
+    let uses_fallback_call = hotpatch_args.uses_fallback_call();
+    let type_of = type_of(&outer, hotpatch_args);
+
     let ImplItemFn {
         attrs,
         vis,
@@ -35,69 +188,335 @@ fn hotpatch_checked(HotpatchFn { inner, outer }: HotpatchFn) -> TokenStream {
         ..
     } = outer;
 
-    let sig_str = quote!(sig).to_string();
-    let sig_lit = LitByteStr::new(sig_str.as_bytes(), Span::call_site());
-
-    let outer_fn = &sig.ident;
     let inner_fn = &inner.sig.ident;
+    let codec = hotpatch_args.codec.path();
 
-    let args = inner.sig.inputs.iter().map(|input| match input {
-        FnArg::Receiver(_) => parse_quote!(self),
-        FnArg::Typed(typed) => fn_input_pat_to_ts(&typed.pat),
-    });
+    let params = match CheckedParam::parse_all(&inner.sig.inputs) {
+        Ok(params) => params,
+        Err(e) => return e.to_compile_error(),
+    };
+
+    let tuple_args_outer = params.iter().map(CheckedParam::outer_arg);
+    let tuple_types_inner = params.iter().map(CheckedParam::deserialized_type);
+    let schema_types: Vec<_> = params.iter().map(|param| &param.owned_type).collect();
+    let output_type = match &inner.sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+    let (impl_generics, _, where_clause) = inner.sig.generics.split_for_impl();
+    let bindings = params.iter().map(CheckedParam::binding);
+    let args = params.iter().map(CheckedParam::inner_arg);
 
-    let tuple_args_outer = args.clone();
-    let tuple_args_inner = args.clone();
+    // `&mut` arguments are serialized after the output and written back to the caller.
+    let write_back = params
+        .iter()
+        .filter(|param| matches!(param.kind, ParamKind::Mut))
+        .collect::<Vec<_>>();
+    let write_back_idents = write_back
+        .iter()
+        .map(|param| &param.ident)
+        .collect::<Vec<_>>();
+    let write_back_outer = write_back.iter().map(|param| &param.pat);
+
+    let (output, write_back) = if write_back_idents.is_empty() {
+        (quote!(output), quote!())
+    } else {
+        (
+            quote!((output, #(#write_back_idents,)*)),
+            quote!(#(*#write_back_outer = #write_back_idents;)*),
+        )
+    };
+
+    let (slot_fn, dispatch) = match uses_fallback_call {
+        false => {
+            let call = |fn_ptr: TokenStream, output_buffer: TokenStream| {
+                quote! {
+                    unsafe {
+                        ::std::mem::transmute::<_, extern "C-unwind" fn(_, _, _) -> bool>(#fn_ptr)(
+                            input.as_ptr(),
+                            input.len(),
+                            &raw mut #output_buffer,
+                        )
+                    }
+                }
+            };
+
+            let dispatch = if hotpatch_args.shadow {
+                // Both implementations are called with the same serialized input, and their
+                // serialized outputs are compared.
+                shadow_dispatch(
+                    call(quote!(fn_ptr), quote!(*output_buffer)),
+                    call(quote!(prev_fn_ptr), quote!(prev_output_buffer)),
+                    (
+                        quote!(libhotpatch::OutputBuffer::new()),
+                        quote!(mut prev_output_buffer),
+                    ),
+                    |output| {
+                        let output_buffer = format_ident!("{output}_buffer");
+                        quote!(&#output.then_some(&#output_buffer[..]))
+                    },
+                )
+            } else {
+                call(quote!(fn_ptr), quote!(*output_buffer))
+            };
+
+            (quote!(checked_call), dispatch)
+        }
+        true => (
+            quote!(fallback_call),
+            fallback_dispatch(
+                hotpatch_args,
+                &[quote!(_), quote!(_), quote!(_)],
+                quote!(),
+                &[
+                    quote!(input.as_ptr()),
+                    quote!(input.len()),
+                    quote!(&raw mut *output_buffer),
+                ],
+            ),
+        ),
+    };
+
+    let on_mismatch = on_mismatch(
+        hotpatch_args,
+        "checked hot-patch input or output deserialization failed",
+    );
+
+    let fallback_call = uses_fallback_call.then(|| {
+        fallback_call(
+            &Generics::default(),
+            &[
+                quote!(ptr: *const u8),
+                quote!(len: usize),
+                quote!(output_buffer: *mut libhotpatch::OutputBuffer),
+            ],
+            quote!(checked_call(ptr, len, output_buffer)),
+        )
+    });
 
     quote! {
         #(#attrs)*
         #vis #defaultness #sig {
-            extern "C-unwind" fn checked_call(ptr: *const u8, len: usize) -> libhotpatch::BoxedSlice<u8> {
+            // Serializes the output into the caller's `output_buffer`. Returns `false` if the
+            // input or output do not match their serialized form.
+            extern "C-unwind" fn checked_call(
+                ptr: *const u8,
+                len: usize,
+                output_buffer: *mut libhotpatch::OutputBuffer,
+            ) -> bool {
                 #inner
-                let (#(#tuple_args_inner,)*) = unsafe {
-                    libhotpatch::rmp_serde::from_slice(::std::slice::from_raw_parts(ptr, len))
-                        .expect("checked hot-patch input deserialization failed")
-                };
-                let output = unsafe {
-                    libhotpatch::rmp_serde::to_vec_named(&#inner_fn(#(#args,)*))
-                        .expect("checked hot-patch output serialization failed")
+                let ::std::result::Result::Ok((#(#bindings,)*)) = (unsafe {
+                    <#codec as libhotpatch::codec::HotpatchCodec>::deserialize::<(#(#tuple_types_inner,)*)>(
+                        ::std::slice::from_raw_parts(ptr, len),
+                    )
+                }) else {
+                    return false;
                 };
-                libhotpatch::BoxedSlice::new(&output)
+                let output = unsafe { #inner_fn(#(#args,)*) };
+                let output_buffer = unsafe { &mut *output_buffer };
+                output_buffer.clear();
+                <#codec as libhotpatch::codec::HotpatchCodec>::serialize(&#output, output_buffer).is_ok()
             }
-            fn type_of() -> (u128, &'static str) {
-                let name = ::std::any::type_name_of_val(&#outer_fn);
-                let mut hasher = libhotpatch::Xxh3::new();
-                ::std::hash::Hash::hash(#sig_lit, &mut hasher);
-                ::std::hash::Hash::hash(name.as_bytes(), &mut hasher);
-                (hasher.digest128(), name)
+            extern "C" fn replay_call(
+                ptr: *const u8,
+                len: usize,
+                output: *mut libhotpatch::BoxedSlice<u8>,
+            ) -> bool {
+                let mut output_buffer = libhotpatch::OutputBuffer::new();
+                match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+                    checked_call(ptr, len, &raw mut output_buffer)
+                })) {
+                    ::std::result::Result::Ok(true) => {
+                        unsafe { ::std::ptr::write(output, libhotpatch::BoxedSlice::new(&output_buffer)) };
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            extern "C" fn schema #impl_generics () -> libhotpatch::BoxedSlice<u8> #where_clause {
+                libhotpatch::describe_schema::<(#(#schema_types,)*), #output_type, #codec>()
             }
+            extern "C" fn probe #impl_generics (
+                ptr: *const u8,
+                len: usize,
+                output: bool,
+            ) -> bool #where_clause {
+                // SAFETY: the schema is borrowed from the generation comparing schemas.
+                let schema = unsafe { ::std::slice::from_raw_parts(ptr, len) };
+                libhotpatch::probe_schema::<(#(#schema_types,)*), #output_type, #codec>(schema, output)
+            }
+            #fallback_call
+            #type_of
             #[libhotpatch::distributed_slice(libhotpatch::HOTPATCH_FN)]
             #[linkme(crate = libhotpatch::linkme)]
-            static HOTPATCH_FN: (
-                ::std::sync::atomic::AtomicPtr<()>,
-                libhotpatch::LibraryHandle,
-                fn() -> (u128, &'static str),
-            ) = (
-                ::std::sync::atomic::AtomicPtr::new(checked_call as *mut ()),
-                libhotpatch::LibraryHandle::null(),
+            static HOTPATCH_FN: libhotpatch::HotpatchSlot = libhotpatch::HotpatchSlot::new_checked(
+                #slot_fn as *mut (),
                 type_of,
+                replay_call,
+                schema,
+                probe,
             );
             libhotpatch::Watcher::get().map(libhotpatch::Watcher::poll);
-            let library_handle = HOTPATCH_FN.1.clone();
-            let serialized = libhotpatch::rmp_serde::to_vec_named(&(#(#tuple_args_outer,)*))
+            let library_handle = HOTPATCH_FN.handle();
+            // Reused between calls on this thread.
+            let mut buffers = libhotpatch::CheckedBuffers::take();
+            let libhotpatch::CheckedBuffers { input, output: output_buffer } = &mut buffers;
+            <#codec as libhotpatch::codec::HotpatchCodec>::serialize(&(#(#tuple_args_outer,)*), &mut *input)
                 .expect("checked hot-patch input serialization failed");
-            let serialized_output = unsafe {
-                ::std::mem::transmute::<_, extern "C-unwind" fn(_, _) -> libhotpatch::BoxedSlice<u8>>(
-                    HOTPATCH_FN.0.load(::std::sync::atomic::Ordering::Relaxed))
-                        (serialized.as_ptr(), serialized.len())
+            loop {
+                let fn_ptr = HOTPATCH_FN.fn_ptr();
+                let succeeded: bool = #dispatch;
+                if succeeded {
+                    HOTPATCH_FN.record(input, output_buffer);
+                    if let ::std::result::Result::Ok(#output) =
+                        <#codec as libhotpatch::codec::HotpatchCodec>::deserialize(output_buffer)
+                    {
+                        #write_back
+                        break output;
+                    }
+                }
+                HOTPATCH_FN.report_mismatch();
+                #on_mismatch
+            }
+        }
+    }
+}
+
+fn hotpatch_archived(HotpatchFn { inner, outer }: HotpatchFn, hotpatch_args: &Args) -> TokenStream {
+    let uses_fallback_call = hotpatch_args.uses_fallback_call();
+    let type_of = type_of(&outer, hotpatch_args);
+
+    let ImplItemFn {
+        attrs,
+        vis,
+        defaultness,
+        sig,
+        ..
+    } = outer;
+
+    let inner_fn = &inner.sig.ident;
+    let (impl_generics, _, where_clause) = inner.sig.generics.split_for_impl();
+
+    let args = inner
+        .sig
+        .inputs
+        .iter()
+        .map(|input| match input {
+            FnArg::Receiver(_) => parse_quote!(self),
+            FnArg::Typed(typed) => fn_input_pat_to_ts(&typed.pat),
+        })
+        .collect::<Vec<_>>();
+
+    let idents = (0..args.len())
+        .map(|i| format_ident!("__arg{i}").into_token_stream())
+        .collect::<Vec<_>>();
+
+    let params = inner
+        .sig
+        .inputs
+        .iter()
+        .zip(&idents)
+        .map(|(input, ident)| match input {
+            FnArg::Receiver(_) => unreachable!(),
+            FnArg::Typed(typed) => {
+                let ty = &typed.ty;
+                quote!(#ident: #ty)
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let wild = args.iter().map(|_| quote!(_)).collect::<Vec<_>>();
+
+    let (slot_fn, fallback_call, dispatch) = match uses_fallback_call {
+        false => {
+            let call = |fn_ptr: TokenStream| {
+                quote! {
+                    unsafe {
+                        ::std::mem::transmute::<
+                            _,
+                            extern "C-unwind" fn(#(#wild,)*) -> ::std::option::Option<libhotpatch::BoxedSlice<u8>>,
+                        >(#fn_ptr)(#(#args,)*)
+                    }
+                }
+            };
+
+            let dispatch = if hotpatch_args.shadow {
+                // Views are `Copy`, both implementations are called with the same views, and
+                // their archived outputs are compared.
+                shadow_dispatch(
+                    call(quote!(fn_ptr)),
+                    call(quote!(prev_fn_ptr)),
+                    (quote!(()), quote!(())),
+                    |output| quote!(&#output.as_deref()),
+                )
+            } else {
+                call(quote!(fn_ptr))
             };
-            libhotpatch::rmp_serde::from_slice(&serialized_output)
-                .expect("checked hot-patch output deserialization failed")
+
+            (quote!(archived_call), None, dispatch)
+        }
+        true => (
+            quote!(fallback_call),
+            Some(fallback_call(
+                &inner.sig.generics,
+                &params,
+                quote!(archived_call(#(#idents,)*)),
+            )),
+            fallback_dispatch(hotpatch_args, &wild, quote!(), &args),
+        ),
+    };
+
+    let on_mismatch = on_mismatch(
+        hotpatch_args,
+        "archived hot-patch input or output validation failed",
+    );
+
+    quote! {
+        #(#attrs)*
+        #vis #defaultness #sig {
+            // Returns `None` if the input or output do not match their archived layout.
+            #[allow(improper_ctypes_definitions)]
+            extern "C-unwind" fn archived_call #impl_generics (
+                #(#params,)*
+            ) -> ::std::option::Option<libhotpatch::BoxedSlice<u8>>
+            #where_clause
+            {
+                #inner
+                #(let #idents = libhotpatch::archived::View::validate(#idents)?;)*
+                libhotpatch::archived::serialize_output(&unsafe { #inner_fn(#(#idents,)*) })
+            }
+            #fallback_call
+            #type_of
+            #[libhotpatch::distributed_slice(libhotpatch::HOTPATCH_FN)]
+            #[linkme(crate = libhotpatch::linkme)]
+            static HOTPATCH_FN: libhotpatch::HotpatchSlot =
+                libhotpatch::HotpatchSlot::new(#slot_fn as *mut (), type_of);
+            libhotpatch::Watcher::get().map(libhotpatch::Watcher::poll);
+            let library_handle = HOTPATCH_FN.handle();
+            loop {
+                let fn_ptr = HOTPATCH_FN.fn_ptr();
+                let archived_output: ::std::option::Option<libhotpatch::BoxedSlice<u8>> =
+                    #dispatch;
+                if let ::std::option::Option::Some(archived_output) = &archived_output
+                    && let ::std::result::Result::Ok(output) =
+                        libhotpatch::archived::deserialize_output(archived_output)
+                {
+                    break output;
+                }
+                HOTPATCH_FN.report_mismatch();
+                #on_mismatch
+            }
         }
     }
 }
 
-fn hotpatch_unchecked(HotpatchFn { mut inner, outer }: HotpatchFn) -> TokenStream {
+fn hotpatch_unchecked(
+    HotpatchFn { mut inner, outer }: HotpatchFn,
+    hotpatch_args: &Args,
+) -> TokenStream {
+    let uses_fallback_call = hotpatch_args.uses_fallback_call();
+    let type_of = type_of(&outer, hotpatch_args);
+
     let ImplItemFn {
         attrs,
         vis,
@@ -119,10 +538,6 @@ fn hotpatch_unchecked(HotpatchFn { mut inner, outer }: HotpatchFn) -> TokenStrea
         })
         .clone();
 
-    let sig_str = quote!(sig).to_string();
-    let sig_lit = LitByteStr::new(sig_str.as_bytes(), Span::call_site());
-
-    let outer_fn = &sig.ident;
     let inner_fn = &inner.sig.ident;
 
     let args = inner.sig.inputs.iter().map(|input| match input {
@@ -130,44 +545,411 @@ fn hotpatch_unchecked(HotpatchFn { mut inner, outer }: HotpatchFn) -> TokenStrea
         FnArg::Typed(typed) => fn_input_pat_to_ts(&typed.pat),
     });
 
-    let wild = inner.sig.inputs.iter().map(|_| PatWild {
-        attrs: vec![],
-        underscore_token: Token![_](Span::call_site()),
-    });
+    let wild = inner
+        .sig
+        .inputs
+        .iter()
+        .map(|_| quote!(_))
+        .collect::<Vec<_>>();
+
+    let (slot_fn, fallback_call, dispatch) = match uses_fallback_call {
+        false => {
+            let call = |fn_ptr: TokenStream, args: &[TokenStream]| {
+                quote! {
+                    unsafe {
+                        ::std::mem::transmute::<_, #abi fn(#(#wild,)*) -> _>(#fn_ptr)
+                            (#(#args,)*)
+                    }
+                }
+            };
+
+            let args = args.collect::<Vec<_>>();
+
+            let dispatch = if hotpatch_args.shadow {
+                let idents = (0..args.len())
+                    .map(|i| format_ident!("__arg{i}").into_token_stream())
+                    .collect::<Vec<_>>();
+
+                // The previous implementation is called with a clone of the arguments.
+                shadow_dispatch(
+                    call(quote!(HOTPATCH_FN.fn_ptr()), &args),
+                    call(quote!(prev_fn_ptr), &idents),
+                    (
+                        quote!(::std::clone::Clone::clone(&(#(#args,)*))),
+                        quote!((#(#idents,)*)),
+                    ),
+                    |output| quote!(&#output),
+                )
+            } else {
+                call(quote!(HOTPATCH_FN.fn_ptr()), &args)
+            };
+
+            (inner_fn.to_token_stream(), None, dispatch)
+        }
+        true => {
+            let idents = (0..inner.sig.inputs.len())
+                .map(|i| format_ident!("__arg{i}").into_token_stream())
+                .collect::<Vec<_>>();
+
+            let params = inner
+                .sig
+                .inputs
+                .iter()
+                .zip(&idents)
+                .map(|(input, ident)| match input {
+                    FnArg::Receiver(_) => unreachable!(),
+                    FnArg::Typed(typed) => {
+                        let ty = &typed.ty;
+                        quote!(#ident: #ty)
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            let fallback_call = fallback_call(
+                &inner.sig.generics,
+                &params,
+                quote!(unsafe { #inner_fn(#(#idents,)*) }),
+            );
+
+            // Every attempt gets its own clone of the arguments.
+            let dispatch = fallback_dispatch(
+                hotpatch_args,
+                &wild,
+                quote!(let (#(#idents,)*) = ::std::clone::Clone::clone(&args);),
+                &idents,
+            );
+
+            (
+                quote!(fallback_call),
+                Some(fallback_call),
+                quote! {{
+                    let args = (#(#args,)*);
+                    #dispatch
+                }},
+            )
+        }
+    };
 
     quote! {
         #(#attrs)*
         #vis #defaultness #sig {
             #inner
-            fn type_of() -> (u128, &'static str) {
-                let name = ::std::any::type_name_of_val(&#outer_fn);
-                let mut hasher = libhotpatch::Xxh3::new();
-                ::std::hash::Hash::hash(#sig_lit, &mut hasher);
-                ::std::hash::Hash::hash(name.as_bytes(), &mut hasher);
-                (hasher.digest128(), name)
-            }
+            #fallback_call
+            #type_of
             #[libhotpatch::distributed_slice(libhotpatch::HOTPATCH_FN)]
             #[linkme(crate = libhotpatch::linkme)]
-            static HOTPATCH_FN: (
-                ::std::sync::atomic::AtomicPtr<()>,
-                libhotpatch::LibraryHandle,
-                fn() -> (u128, &'static str),
-            ) = (
-                ::std::sync::atomic::AtomicPtr::new(#inner_fn as *mut ()),
-                libhotpatch::LibraryHandle::null(),
-                type_of,
-            );
+            static HOTPATCH_FN: libhotpatch::HotpatchSlot =
+                libhotpatch::HotpatchSlot::new(#slot_fn as *mut (), type_of);
             libhotpatch::Watcher::get().map(libhotpatch::Watcher::poll);
-            let library_handle = HOTPATCH_FN.1.clone();
-            unsafe {
-                ::std::mem::transmute::<_, #abi fn(#(#wild,)*) -> _>(
-                    HOTPATCH_FN.0.load(::std::sync::atomic::Ordering::Relaxed))
-                        (#(#args,)*)
+            let library_handle = HOTPATCH_FN.handle();
+            #dispatch
+        }
+    }
+}
+
+/// Handles a `checked` or `archived` call whose input or output failed to deserialize,
+/// according to `on_mismatch`.
+fn on_mismatch(args: &Args, message: &str) -> TokenStream {
+    let fallback_message = format!("{message} with no previous generation to fall back to");
+
+    match args.on_mismatch {
+        OnMismatch::Panic => quote!(::std::panic!(#message);),
+        OnMismatch::Fallback => quote! {
+            if !HOTPATCH_FN.revert_mismatched(fn_ptr) {
+                ::std::panic!(#fallback_message);
+            }
+        },
+        OnMismatch::Default => quote!(break ::std::default::Default::default();),
+    }
+}
+
+/// Generates the `type_of` function, which identifies a hot-patch function across builds.
+fn type_of(outer: &ImplItemFn, args: &Args) -> TokenStream {
+    let sig_str = quote!(sig).to_string();
+    let sig_lit = LitByteStr::new(sig_str.as_bytes(), Span::call_site());
+
+    // Functions with a different dispatch ABI must never be matched.
+    let abi_lit = LitByteStr::new(args.dispatch_abi().as_bytes(), Span::call_site());
+
+    let outer_fn = &outer.sig.ident;
+
+    quote! {
+        fn type_of() -> (u128, &'static str) {
+            let name = ::std::any::type_name_of_val(&#outer_fn);
+            let mut hasher = libhotpatch::Xxh3::new();
+            ::std::hash::Hash::hash(#sig_lit, &mut hasher);
+            ::std::hash::Hash::hash(#abi_lit, &mut hasher);
+            ::std::hash::Hash::hash(name.as_bytes(), &mut hasher);
+            (hasher.digest128(), name)
+        }
+    }
+}
+
+/// Generates `fallback_call`, which evaluates `call` and catches any panic.
+///
+/// The panic must be caught in the generation that raised it, since every generation links
+/// its own copy of `std`. The output is written through the `output` pointer, the panic
+/// payload is returned to the caller.
+fn fallback_call(generics: &Generics, params: &[TokenStream], call: TokenStream) -> TokenStream {
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+    quote! {
+        #[allow(improper_ctypes_definitions)]
+        extern "C-unwind" fn fallback_call #impl_generics (
+            output: *mut (),
+            #(#params,)*
+        ) -> ::std::option::Option<::std::boxed::Box<dyn ::std::any::Any + ::std::marker::Send>>
+        #where_clause
+        {
+            match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| #call)) {
+                ::std::result::Result::Ok(value) => {
+                    unsafe { ::std::ptr::write(output.cast(), value) };
+                    ::std::option::Option::None
+                }
+                ::std::result::Result::Err(payload) => ::std::option::Option::Some(payload),
             }
         }
     }
 }
 
+/// Calls `fallback_call` through the function slot until it does not panic or crash.
+///
+/// After a panic with `fallback_on_panic`, the function is reverted to the previous
+/// generation and called again, or the panic is resumed if there is no previous generation
+/// to revert to. With `crash_guard`, a fault reverts the whole generation instead, or panics
+/// if there is none. `prepare` is evaluated before every attempt.
+fn fallback_dispatch(
+    args: &Args,
+    wild: &[TokenStream],
+    prepare: TokenStream,
+    call_args: &[TokenStream],
+) -> TokenStream {
+    let call = quote! {
+        || unsafe {
+            ::std::mem::transmute::<
+                _,
+                extern "C-unwind" fn(*mut (), #(#wild,)*) -> ::std::option::Option<
+                    ::std::boxed::Box<dyn ::std::any::Any + ::std::marker::Send>,
+                >,
+            >(fn_ptr)((&raw mut output).cast(), #(#call_args,)*)
+        }
+    };
+
+    let call = if args.crash_guard {
+        quote! {
+            match library_handle.guard_crashes(#call) {
+                ::std::result::Result::Ok(payload) => payload,
+                ::std::result::Result::Err(crash) => {
+                    if !HOTPATCH_FN.revert_crashed(fn_ptr, &crash) {
+                        ::std::panic!("hot-patched function crashed ({crash}) with no previous generation to fall back to");
+                    }
+                    continue;
+                }
+            }
+        }
+    } else {
+        quote!((#call)())
+    };
+
+    let on_panic = match args.fallback_on_panic {
+        None => quote!(::std::panic::resume_unwind(payload)),
+        Some(scope) => {
+            let scope = match scope {
+                FallbackScope::Function => quote!(libhotpatch::FallbackScope::Function),
+                FallbackScope::Generation => quote!(libhotpatch::FallbackScope::Generation),
+            };
+
+            quote! {
+                if !HOTPATCH_FN.revert(fn_ptr, #scope, &*payload) {
+                    ::std::panic::resume_unwind(payload);
+                }
+            }
+        }
+    };
+
+    quote! {{
+        let mut output = ::std::mem::MaybeUninit::uninit();
+        loop {
+            let library_handle = HOTPATCH_FN.handle();
+            let fn_ptr = HOTPATCH_FN.fn_ptr();
+            #prepare
+            let payload = #call;
+            match payload {
+                ::std::option::Option::None => break unsafe { output.assume_init() },
+                ::std::option::Option::Some(payload) => #on_panic,
+            }
+        }
+    }}
+}
+
+/// Calls the current implementation through the function slot, then the implementation of
+/// the previous generation, if there is one, and reports if their outputs diverge.
+///
+/// `save` is evaluated before the current implementation is called, and its value is
+/// destructured with the `restore` pattern before calling the previous implementation.
+/// Outputs are compared by the references that `compare` makes of them.
+fn shadow_dispatch(
+    current: TokenStream,
+    previous: TokenStream,
+    (save, restore): (TokenStream, TokenStream),
+    compare: impl Fn(TokenStream) -> TokenStream,
+) -> TokenStream {
+    let output = compare(quote!(output));
+    let prev_output = compare(quote!(prev_output));
+
+    quote! {{
+        let previous = HOTPATCH_FN.previous();
+        let saved = previous.as_ref().map(|_| #save);
+        let output = #current;
+        if let (
+            ::std::option::Option::Some(prev_fn_ptr),
+            ::std::option::Option::Some(#restore),
+        ) = (previous, saved) {
+            let prev_output = #previous;
+            HOTPATCH_FN.report_shadow(#output, #prev_output);
+        }
+        output
+    }}
+}
+
+/// How a parameter of a `checked` function is passed.
+enum ParamKind {
+    Value,
+    /// `&T`, deserialized into an owned `T`.
+    Ref,
+    /// `&mut T`, deserialized into an owned `T` that is written back after the call.
+    Mut,
+}
+
+/// A parameter of a `checked` function.
+struct CheckedParam {
+    kind: ParamKind,
+    /// The outer function binding of the parameter.
+    pat: TokenStream,
+    /// The owned type deserialized for the parameter.
+    owned_type: TokenStream,
+    /// The deserialized value in `checked_call`.
+    ident: Ident,
+}
+
+impl CheckedParam {
+    fn parse_all(inputs: &Punctuated<FnArg, Token![,]>) -> syn::Result<Vec<Self>> {
+        inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| {
+                let FnArg::Typed(typed) = input else {
+                    return Err(syn::Error::new_spanned(input, "unsupported receiver"));
+                };
+
+                let pat = fn_input_pat_to_ts(&typed.pat);
+                let ident = format_ident!("__arg{i}");
+
+                let Type::Reference(reference) = &*typed.ty else {
+                    let ty = &typed.ty;
+
+                    return Ok(Self {
+                        kind: ParamKind::Value,
+                        pat,
+                        owned_type: quote!(#ty),
+                        ident,
+                    });
+                };
+
+                let elem = &reference.elem;
+
+                if reference.mutability.is_none() {
+                    // Unsized types are deserialized into their owned counterparts, which
+                    // deref to them.
+                    let owned_type = match &**elem {
+                        Type::Slice(slice) => {
+                            let elem = &slice.elem;
+                            quote!(::std::vec::Vec<#elem>)
+                        }
+                        Type::Path(path) if path.path.is_ident("str") => {
+                            quote!(::std::string::String)
+                        }
+                        _ => quote!(#elem),
+                    };
+
+                    return Ok(Self {
+                        kind: ParamKind::Ref,
+                        pat,
+                        owned_type,
+                        ident,
+                    });
+                }
+
+                if !matches!(&*typed.pat, Pat::Ident(_)) {
+                    return Err(syn::Error::new_spanned(
+                        &typed.pat,
+                        "a `&mut` parameter of a checked function must be bound to an identifier",
+                    ));
+                }
+
+                if matches!(&**elem, Type::Slice(_))
+                    || matches!(&**elem, Type::Path(path) if path.path.is_ident("str"))
+                {
+                    return Err(syn::Error::new_spanned(
+                        elem,
+                        "a `&mut` parameter of a checked function must reference a sized type",
+                    ));
+                }
+
+                Ok(Self {
+                    kind: ParamKind::Mut,
+                    pat,
+                    owned_type: quote!(#elem),
+                    ident,
+                })
+            })
+            .collect()
+    }
+
+    /// The serialized argument in the outer function.
+    fn outer_arg(&self) -> TokenStream {
+        let pat = &self.pat;
+
+        match self.kind {
+            // Reborrowed, so that the output can be written back to it.
+            ParamKind::Mut => quote!(&*#pat),
+            ParamKind::Value | ParamKind::Ref => quote!(#pat),
+        }
+    }
+
+    /// The type annotation of the deserialized argument in `checked_call`.
+    ///
+    /// The types of arguments passed by value are inferred, since they may name lifetimes
+    /// of the function, which `checked_call` does not have.
+    fn deserialized_type(&self) -> TokenStream {
+        match self.kind {
+            ParamKind::Value => quote!(_),
+            ParamKind::Ref | ParamKind::Mut => self.owned_type.clone(),
+        }
+    }
+
+    /// The binding of the deserialized argument in `checked_call`.
+    fn binding(&self) -> TokenStream {
+        let ident = &self.ident;
+
+        match self.kind {
+            ParamKind::Mut => quote!(mut #ident),
+            ParamKind::Value | ParamKind::Ref => quote!(#ident),
+        }
+    }
+
+    /// The argument passed to the inner function in `checked_call`.
+    fn inner_arg(&self) -> TokenStream {
+        let ident = &self.ident;
+
+        match self.kind {
+            ParamKind::Value => quote!(#ident),
+            ParamKind::Ref => quote!(&#ident),
+            ParamKind::Mut => quote!(&mut #ident),
+        }
+    }
+}
+
 fn fn_input_pat_to_ts(pat: &Pat) -> TokenStream {
     match pat {
         Pat::Ident(pat_ident) => pat_ident.ident.clone().to_token_stream(),
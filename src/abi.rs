@@ -1,3 +1,5 @@
 pub mod boxed;
+#[cfg(feature = "checked")]
+pub mod buffer;
 pub mod str;
 pub mod time;
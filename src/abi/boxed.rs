@@ -42,7 +42,7 @@ impl<T> Box<T> {
         ptr
     }
 
-    /// # Safety:
+    /// # Safety
     ///
     /// `ptr` must be derived from a previous call to [`Box::into_raw`] and this method must not
     /// be called more than once on such pointer.
@@ -79,7 +79,7 @@ impl<T: Copy> BoxedSlice<T> {
         ptr::slice_from_raw_parts_mut(ptr, len)
     }
 
-    /// # Safety:
+    /// # Safety
     ///
     /// `ptr` must be derived from a previous call to [`Box::into_raw`] and this method must not
     /// be called more than once on such pointer.
@@ -0,0 +1,142 @@
+use std::{
+    alloc::{Layout, handle_alloc_error},
+    cell::Cell,
+    ffi::c_void,
+    io, mem,
+    ops::Deref,
+    ptr::{self, NonNull},
+    slice,
+};
+
+use crate::os::{aligned_alloc, free};
+
+/// A byte buffer that is owned by the caller of a `checked` call and filled by the callee.
+///
+/// It grows with the allocator shared by all generations, so that the generation that fills
+/// it need not be the one that frees it.
+#[repr(C)]
+pub struct OutputBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+    cap: usize,
+}
+
+/// The buffers of an outer `checked` call, reused between calls on the same thread.
+///
+/// Nested calls on the same thread get new buffers, which replace the reused ones when they
+/// are dropped last.
+pub struct CheckedBuffers {
+    pub input: Vec<u8>,
+    pub output: OutputBuffer,
+}
+
+thread_local! {
+    static CHECKED_BUFFERS: Cell<(Vec<u8>, OutputBuffer)> =
+        const { Cell::new((Vec::new(), OutputBuffer::new())) };
+}
+
+impl OutputBuffer {
+    pub const fn new() -> Self {
+        Self {
+            ptr: NonNull::dangling(),
+            len: 0,
+            cap: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        let required = self.len.checked_add(additional).expect("capacity overflow");
+
+        if required <= self.cap {
+            return;
+        }
+
+        let cap = required.max(self.cap * 2).max(64);
+
+        let Some(ptr) = NonNull::new(aligned_alloc(cap, 1) as *mut u8) else {
+            handle_alloc_error(Layout::array::<u8>(cap).unwrap());
+        };
+
+        // SAFETY: copying the initialized bytes into a brand new allocation that is larger.
+        unsafe {
+            ptr::copy_nonoverlapping(self.ptr.as_ptr(), ptr.as_ptr(), self.len);
+        }
+
+        self.free();
+        self.ptr = ptr;
+        self.cap = cap;
+    }
+
+    fn free(&mut self) {
+        if self.cap != 0 {
+            // SAFETY: this pointer could have only been allocated with the corresponding `malloc`.
+            unsafe { free(self.ptr.as_ptr() as *mut c_void) }
+        }
+    }
+}
+
+impl CheckedBuffers {
+    /// Takes the buffers of the current thread, cleared.
+    pub fn take() -> Self {
+        let (mut input, mut output) = CHECKED_BUFFERS.try_with(Cell::take).unwrap_or_default();
+
+        input.clear();
+        output.clear();
+
+        Self { input, output }
+    }
+}
+
+impl Default for OutputBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for OutputBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: the buffer is initialized and valid for up to `self.len`.
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl io::Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.reserve(buf.len());
+
+        // SAFETY: `reserve` made room for `buf` after the initialized bytes.
+        unsafe {
+            ptr::copy_nonoverlapping(buf.as_ptr(), self.ptr.as_ptr().add(self.len), buf.len());
+        }
+
+        self.len += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for OutputBuffer {
+    fn drop(&mut self) {
+        self.free();
+    }
+}
+
+impl Drop for CheckedBuffers {
+    fn drop(&mut self) {
+        let buffers = (mem::take(&mut self.input), mem::take(&mut self.output));
+        let _ = CHECKED_BUFFERS.try_with(|cell| cell.set(buffers));
+    }
+}
+
+unsafe impl Send for OutputBuffer {}
+
+unsafe impl Sync for OutputBuffer {}
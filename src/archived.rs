@@ -0,0 +1,242 @@
+//! Zero-copy arguments of `#[hotpatch(archived)]` functions.
+//!
+//! Large inputs are archived once with `rkyv` into an [`ArchivedBuf`], and passed to
+//! `archived` functions as [`View`]s of it. When a view crosses into another build, its
+//! bytes are validated against the archived layout that build expects, without being
+//! deserialized or copied. Each buffer is validated at most once by every build that reads
+//! it.
+
+use std::{
+    io,
+    marker::PhantomData,
+    mem, slice,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use rkyv::{
+    Archive, Deserialize, Serialize,
+    api::high::{HighSerializer, HighValidator},
+    bytecheck::CheckBytes,
+    rancor,
+    ser::allocator::ArenaHandle,
+    util::AlignedVec,
+};
+
+use crate::abi::boxed::BoxedSlice;
+
+/// How many builds a buffer remembers having validated it.
+const VALIDATED_LEN: usize = 4;
+
+/// An owned buffer holding a `T` in its archived form.
+pub struct ArchivedBuf<T> {
+    bytes: AlignedVec,
+    /// The builds that validated the buffer, see [`View::validator`].
+    validated: Validated,
+    _marker: PhantomData<T>,
+}
+
+/// The validators that accepted a buffer, replaced in turn when all slots are taken.
+#[repr(C)]
+struct Validated {
+    validators: [AtomicUsize; VALIDATED_LEN],
+    next: AtomicUsize,
+}
+
+/// A borrowed view of an archived `T`, the argument type of `archived` functions.
+///
+/// Its layout does not depend on `T`, so it can be passed between builds in which `T`
+/// differs. Such views are validated before the function of the other build is called.
+#[repr(C)]
+pub struct View<'a, T> {
+    ptr: *const u8,
+    len: usize,
+    archived_size: usize,
+    validated: *const Validated,
+    _marker: PhantomData<&'a T>,
+}
+
+impl<T: Archive> ArchivedBuf<T>
+where
+    T::Archived: for<'a> CheckBytes<HighValidator<'a, rancor::Error>>,
+{
+    /// Archives `value`.
+    pub fn new(value: &T) -> io::Result<Self>
+    where
+        T: for<'a> Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, rancor::Error>>,
+    {
+        let bytes = rkyv::to_bytes::<rancor::Error>(value).map_err(invalid_data)?;
+
+        Ok(Self::new_valid(bytes))
+    }
+
+    /// Takes ownership of bytes archived elsewhere, after validating them.
+    pub fn from_bytes(bytes: AlignedVec) -> io::Result<Self> {
+        rkyv::access::<T::Archived, rancor::Error>(&bytes).map_err(invalid_data)?;
+
+        Ok(Self::new_valid(bytes))
+    }
+
+    /// Wraps bytes holding a valid `T::Archived`, which this build need not validate again.
+    fn new_valid(bytes: AlignedVec) -> Self {
+        let validated = Validated {
+            validators: Default::default(),
+            next: AtomicUsize::new(0),
+        };
+        validated.insert(View::<T>::validator());
+
+        Self {
+            bytes,
+            validated,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn view(&self) -> View<'_, T> {
+        View {
+            ptr: self.bytes.as_ptr(),
+            len: self.bytes.len(),
+            archived_size: mem::size_of::<T::Archived>(),
+            validated: &self.validated,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn get(&self) -> &T::Archived {
+        self.view().get()
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl<'a, T: Archive> View<'a, T>
+where
+    T::Archived: for<'b> CheckBytes<HighValidator<'b, rancor::Error>>,
+{
+    pub fn get(&self) -> &'a T::Archived {
+        // SAFETY: views are only created from buffers holding a valid `T::Archived`, or are
+        // validated by `validate` when they come from another build.
+        unsafe { rkyv::access_unchecked::<T::Archived>(self.bytes()) }
+    }
+
+    fn bytes(&self) -> &'a [u8] {
+        // SAFETY: the view borrows the buffer it was created from.
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    #[doc(hidden)]
+    pub fn validate(self) -> Option<Self> {
+        // SAFETY: the view borrows the buffer it was created from.
+        let validated = unsafe { &*self.validated };
+        let validator = Self::validator();
+
+        if validated.contains(validator) {
+            return Some(self);
+        }
+
+        if self.archived_size != mem::size_of::<T::Archived>() {
+            return None;
+        }
+
+        rkyv::access::<T::Archived, rancor::Error>(self.bytes()).ok()?;
+        validated.insert(validator);
+
+        Some(self)
+    }
+
+    /// Identifies the layout this build validates views against, by the address of its
+    /// `validate`, which is distinct for every build and type since builds are never
+    /// unloaded. Should two types share the same code, they also accept the same buffers.
+    fn validator() -> usize {
+        Self::validate as fn(Self) -> Option<Self> as usize
+    }
+}
+
+impl Validated {
+    fn contains(&self, validator: usize) -> bool {
+        self.validators
+            .iter()
+            .any(|slot| slot.load(Ordering::Relaxed) == validator)
+    }
+
+    fn insert(&self, validator: usize) {
+        let next = self.next.fetch_add(1, Ordering::Relaxed);
+        self.validators[next % VALIDATED_LEN].store(validator, Ordering::Relaxed);
+    }
+}
+
+impl<T> Clone for View<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for View<'_, T> {}
+
+unsafe impl<T: Sync> Send for View<'_, T> {}
+
+unsafe impl<T: Sync> Sync for View<'_, T> {}
+
+/// Archives the output of an `archived` function.
+#[doc(hidden)]
+pub fn serialize_output<T>(value: &T) -> Option<BoxedSlice<u8>>
+where
+    T: for<'a> Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, rancor::Error>>,
+{
+    let bytes = rkyv::to_bytes::<rancor::Error>(value).ok()?;
+    Some(BoxedSlice::new(&bytes))
+}
+
+/// Validates and deserializes the output of an `archived` function.
+#[doc(hidden)]
+pub fn deserialize_output<T: Archive>(bytes: &[u8]) -> io::Result<T>
+where
+    T::Archived: for<'a> CheckBytes<HighValidator<'a, rancor::Error>>
+        + Deserialize<T, rancor::Strategy<rkyv::de::Pool, rancor::Error>>,
+{
+    // The output is copied to meet the alignment of its archived form.
+    let mut aligned = AlignedVec::<16>::with_capacity(bytes.len());
+    aligned.extend_from_slice(bytes);
+
+    rkyv::from_bytes::<T, rancor::Error>(&aligned).map_err(invalid_data)
+}
+
+fn invalid_data(e: rancor::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reinterprets a view as one of another build, whose archived type differs.
+    fn view_as<T, U>(view: View<'_, T>) -> View<'_, U> {
+        // SAFETY: the layout of views does not depend on their type.
+        unsafe { mem::transmute(view) }
+    }
+
+    #[test]
+    fn validate_own_view() {
+        let buf = ArchivedBuf::new(&7u32).unwrap();
+
+        assert_eq!(*buf.view().validate().unwrap().get(), 7);
+    }
+
+    #[test]
+    fn validate_view_of_other_type() {
+        let surrogate = ArchivedBuf::new(&0xd800u32).unwrap();
+        let letter = ArchivedBuf::new(&u32::from('a')).unwrap();
+
+        assert!(view_as::<u32, char>(surrogate.view()).validate().is_none());
+        assert!(view_as::<u32, u64>(letter.view()).validate().is_none());
+
+        let view = view_as::<u32, char>(letter.view()).validate().unwrap();
+        assert_eq!(*view.get(), 'a');
+        assert!(view.validate().is_some());
+
+        // The buffer is still valid for its own type, validated or not by another build.
+        assert!(letter.view().validate().is_some());
+        assert!(surrogate.view().validate().is_some());
+    }
+}
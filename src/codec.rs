@@ -0,0 +1,150 @@
+//! Serialization formats of `#[hotpatch(checked)]` function inputs and outputs.
+
+use std::io;
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::config::{Codec, Config};
+
+/// A serialization format for the inputs and outputs of `checked` functions.
+///
+/// Values are serialized into buffers that are reused between calls, so `serialize` should
+/// write to `writer` directly instead of allocating.
+///
+/// Both builds taking part in a call must use the same codec. Codecs are selected per
+/// function with `#[hotpatch(checked = "...")]`, by name or by the path of a type
+/// implementing this trait.
+pub trait HotpatchCodec {
+    fn serialize<T: Serialize + ?Sized>(value: &T, writer: impl io::Write) -> io::Result<()>;
+
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> io::Result<T>;
+
+    /// Whether struct fields and enum variants are encoded by name rather than by position,
+    /// which lets fields be added, removed and reordered between builds.
+    fn encodes_names() -> bool {
+        false
+    }
+}
+
+/// MessagePack with named struct fields, using `rmp-serde` (`checked = "messagepack"`).
+pub struct MessagePack;
+
+/// `bincode` with its standard configuration (`checked = "bincode"`).
+#[cfg(feature = "bincode")]
+pub struct Bincode;
+
+/// `postcard` (`checked = "postcard"`).
+#[cfg(feature = "postcard")]
+pub struct Postcard;
+
+/// JSON, using `serde_json`, which is slow but readable in recordings (`checked = "json"`).
+#[cfg(feature = "json")]
+pub struct Json;
+
+/// The codec selected by the `LIBHOTPATCH_CODEC` environment variable, MessagePack by
+/// default (`checked`).
+pub struct Configured;
+
+impl HotpatchCodec for MessagePack {
+    fn serialize<T: Serialize + ?Sized>(value: &T, mut writer: impl io::Write) -> io::Result<()> {
+        rmp_serde::encode::write_named(&mut writer, value).map_err(invalid_data)
+    }
+
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> io::Result<T> {
+        rmp_serde::from_slice(bytes).map_err(invalid_data)
+    }
+
+    fn encodes_names() -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl HotpatchCodec for Bincode {
+    fn serialize<T: Serialize + ?Sized>(value: &T, mut writer: impl io::Write) -> io::Result<()> {
+        bincode::serde::encode_into_std_write(value, &mut writer, bincode::config::standard())
+            .map(drop)
+            .map_err(invalid_data)
+    }
+
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> io::Result<T> {
+        let (value, len) = bincode::serde::decode_from_slice(bytes, bincode::config::standard())
+            .map_err(invalid_data)?;
+
+        if len != bytes.len() {
+            return Err(invalid_data("trailing bytes after bincode value"));
+        }
+
+        Ok(value)
+    }
+}
+
+#[cfg(feature = "postcard")]
+impl HotpatchCodec for Postcard {
+    fn serialize<T: Serialize + ?Sized>(value: &T, writer: impl io::Write) -> io::Result<()> {
+        postcard::to_io(value, writer)
+            .map(drop)
+            .map_err(invalid_data)
+    }
+
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> io::Result<T> {
+        postcard::from_bytes(bytes).map_err(invalid_data)
+    }
+}
+
+#[cfg(feature = "json")]
+impl HotpatchCodec for Json {
+    fn serialize<T: Serialize + ?Sized>(value: &T, writer: impl io::Write) -> io::Result<()> {
+        serde_json::to_writer(writer, value).map_err(invalid_data)
+    }
+
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> io::Result<T> {
+        serde_json::from_slice(bytes).map_err(invalid_data)
+    }
+
+    fn encodes_names() -> bool {
+        true
+    }
+}
+
+impl HotpatchCodec for Configured {
+    fn serialize<T: Serialize + ?Sized>(value: &T, writer: impl io::Write) -> io::Result<()> {
+        match Config::get().codec {
+            Codec::MessagePack => MessagePack::serialize(value, writer),
+            #[cfg(feature = "bincode")]
+            Codec::Bincode => Bincode::serialize(value, writer),
+            #[cfg(feature = "postcard")]
+            Codec::Postcard => Postcard::serialize(value, writer),
+            #[cfg(feature = "json")]
+            Codec::Json => Json::serialize(value, writer),
+        }
+    }
+
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> io::Result<T> {
+        match Config::get().codec {
+            Codec::MessagePack => MessagePack::deserialize(bytes),
+            #[cfg(feature = "bincode")]
+            Codec::Bincode => Bincode::deserialize(bytes),
+            #[cfg(feature = "postcard")]
+            Codec::Postcard => Postcard::deserialize(bytes),
+            #[cfg(feature = "json")]
+            Codec::Json => Json::deserialize(bytes),
+        }
+    }
+
+    fn encodes_names() -> bool {
+        match Config::get().codec {
+            Codec::MessagePack => MessagePack::encodes_names(),
+            #[cfg(feature = "bincode")]
+            Codec::Bincode => Bincode::encodes_names(),
+            #[cfg(feature = "postcard")]
+            Codec::Postcard => Postcard::encodes_names(),
+            #[cfg(feature = "json")]
+            Codec::Json => Json::encodes_names(),
+        }
+    }
+}
+
+fn invalid_data(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
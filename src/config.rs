@@ -0,0 +1,278 @@
+use std::{env, str::FromStr, sync::LazyLock, time::Duration};
+
+/// Runtime configuration, read once from `LIBHOTPATCH_*` environment variables.
+#[derive(Debug)]
+pub struct Config {
+    /// Maximum number of scratch directories kept in `.hotpatch`.
+    pub scratch_max_count: Option<usize>,
+    /// Maximum total size in bytes of the scratch directories kept in `.hotpatch`.
+    pub scratch_max_bytes: Option<u64>,
+    /// How new builds of the library are loaded.
+    pub loader: Loader,
+    /// How long the size and modification time of a new build must stay unchanged
+    /// before it is loaded.
+    pub debounce: Duration,
+    /// Flags passed to `dlopen` when loading new builds (Unix only).
+    pub load_flags: LoadFlags,
+    /// Load new builds in a forked child process before loading them in the host (Linux only).
+    pub validate_in_child: bool,
+    /// Record the serialized input and output of every call of a `checked` function.
+    pub record: bool,
+    /// Maximum size in bytes of the recorded calls kept of each function.
+    pub record_max_bytes: u64,
+    /// Whether recorded calls are replayed against new builds before they are patched in.
+    pub replay: Replay,
+    /// The codec of `checked` functions that do not select one.
+    #[cfg_attr(not(feature = "checked"), allow(dead_code))]
+    pub codec: Codec,
+    /// Which changes of the schemas of `checked` functions new builds may make.
+    #[cfg_attr(not(feature = "checked"), allow(dead_code))]
+    pub schema_evolution: SchemaEvolution,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Loader {
+    /// Copy the library into a scratch directory and load the copy.
+    #[default]
+    TempFile,
+    /// Load the library from an anonymous in-memory file (Linux only).
+    Memfd,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Replay {
+    /// Do not replay recorded calls.
+    #[default]
+    Off,
+    /// Report functions whose outputs diverge from the recorded ones.
+    Warn,
+    /// Reject builds with functions whose outputs diverge from the recorded ones.
+    Reject,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaEvolution {
+    /// Report changes, and let calls that fail to deserialize be handled by `on_mismatch`.
+    #[default]
+    Warn,
+    /// Reject builds with changes that make values of either build fail to deserialize in
+    /// the other.
+    Tolerant,
+    /// Reject builds with any change.
+    Strict,
+}
+
+/// Serialization formats of `checked` functions, see `crate::codec`.
+///
+/// Only the formats of enabled features can be selected.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    #[default]
+    MessagePack,
+    #[cfg(feature = "bincode")]
+    Bincode,
+    #[cfg(feature = "postcard")]
+    Postcard,
+    #[cfg(feature = "json")]
+    Json,
+}
+
+/// The `dlopen` flags builds are loaded with.
+///
+/// `RTLD_NODELETE` is always set, since values of persistent statics, trait objects and
+/// panics refer to the code of builds that are no longer active.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoadFlags {
+    /// Resolve all undefined symbols when loading (`RTLD_NOW`), instead of on first use.
+    pub bind_now: bool,
+    /// Make the library's symbols available to subsequently loaded libraries (`RTLD_GLOBAL`).
+    pub global: bool,
+    /// Prefer the library's own symbols over global ones (`RTLD_DEEPBIND`, glibc only).
+    pub deep_bind: bool,
+}
+
+impl Config {
+    pub fn get() -> &'static Config {
+        static CONFIG: LazyLock<Config> = LazyLock::new(Config::from_env);
+        &CONFIG
+    }
+
+    fn from_env() -> Self {
+        Self {
+            scratch_max_count: env_var("LIBHOTPATCH_SCRATCH_MAX_COUNT"),
+            scratch_max_bytes: env_var("LIBHOTPATCH_SCRATCH_MAX_BYTES"),
+            loader: env_var("LIBHOTPATCH_LOADER").unwrap_or_default(),
+            debounce: Duration::from_millis(env_var("LIBHOTPATCH_DEBOUNCE_MS").unwrap_or(100)),
+            load_flags: env_var("LIBHOTPATCH_DLOPEN_FLAGS").unwrap_or_default(),
+            validate_in_child: env_flag("LIBHOTPATCH_VALIDATE_IN_CHILD"),
+            record: env_flag("LIBHOTPATCH_RECORD"),
+            record_max_bytes: env_var("LIBHOTPATCH_RECORD_MAX_BYTES").unwrap_or(1 << 20),
+            replay: env_var("LIBHOTPATCH_REPLAY").unwrap_or_default(),
+            codec: env_var("LIBHOTPATCH_CODEC").unwrap_or_default(),
+            schema_evolution: env_var("LIBHOTPATCH_SCHEMA_EVOLUTION").unwrap_or_default(),
+        }
+    }
+}
+
+/// A boolean configuration value.
+#[derive(Debug, PartialEq, Eq)]
+struct Flag(bool);
+
+impl FromStr for Flag {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Ok(Self(true)),
+            "0" | "false" | "no" | "off" => Ok(Self(false)),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for Loader {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tempfile" => Ok(Self::TempFile),
+            "memfd" => Ok(Self::Memfd),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for Replay {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "reject" => Ok(Self::Reject),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for SchemaEvolution {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warn" => Ok(Self::Warn),
+            "tolerant" => Ok(Self::Tolerant),
+            "strict" => Ok(Self::Strict),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for Codec {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "messagepack" => Ok(Self::MessagePack),
+            #[cfg(feature = "bincode")]
+            "bincode" => Ok(Self::Bincode),
+            #[cfg(feature = "postcard")]
+            "postcard" => Ok(Self::Postcard),
+            #[cfg(feature = "json")]
+            "json" => Ok(Self::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for LoadFlags {
+    type Err = ();
+
+    /// Parses a comma separated list of `lazy`, `now`, `local`, `global`, `nodelete` and
+    /// `deepbind`, applied over the defaults. `nodelete` is accepted but changes nothing,
+    /// since it is always set.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut flags = Self::default();
+
+        for flag in s.split(',').map(str::trim).filter(|flag| !flag.is_empty()) {
+            match flag {
+                "lazy" => flags.bind_now = false,
+                "now" => flags.bind_now = true,
+                "local" => flags.global = false,
+                "global" => flags.global = true,
+                "nodelete" => {}
+                "deepbind" => flags.deep_bind = true,
+                _ => return Err(()),
+            }
+        }
+
+        Ok(flags)
+    }
+}
+
+fn env_flag(name: &str) -> bool {
+    env_var(name).is_some_and(|Flag(flag)| flag)
+}
+
+fn env_var<T: FromStr>(name: &str) -> Option<T> {
+    let value = env::var(name).ok()?;
+
+    value
+        .trim()
+        .parse()
+        .inspect_err(|_| log::warn!("ignoring invalid value {value:?} of {name}"))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_flags() {
+        for value in ["1", "true", "TRUE", "yes", "on"] {
+            assert_eq!(value.parse(), Ok(Flag(true)), "{value}");
+        }
+
+        for value in ["0", "false", "No", "off"] {
+            assert_eq!(value.parse(), Ok(Flag(false)), "{value}");
+        }
+
+        for value in ["", "2", "enabled"] {
+            assert_eq!(value.parse::<Flag>(), Err(()), "{value}");
+        }
+    }
+
+    #[test]
+    fn parse_default_load_flags() {
+        assert_eq!("".parse(), Ok(LoadFlags::default()));
+        assert_eq!(" , ".parse(), Ok(LoadFlags::default()));
+        assert_eq!("local,lazy,nodelete".parse(), Ok(LoadFlags::default()));
+    }
+
+    #[test]
+    fn parse_load_flags() {
+        assert_eq!(
+            "now".parse(),
+            Ok(LoadFlags {
+                bind_now: true,
+                ..LoadFlags::default()
+            })
+        );
+
+        assert_eq!(
+            "global, deepbind".parse(),
+            Ok(LoadFlags {
+                bind_now: false,
+                global: true,
+                deep_bind: true,
+            })
+        );
+    }
+
+    #[test]
+    fn reject_unknown_load_flags() {
+        assert_eq!("now,delete".parse::<LoadFlags>(), Err(()));
+        assert_eq!("NOW".parse::<LoadFlags>(), Err(()));
+    }
+}
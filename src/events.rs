@@ -0,0 +1,79 @@
+use std::sync::{Arc, PoisonError, RwLock};
+
+use crate::persistent::Persistent;
+
+/// Something noteworthy that happened while calling a hot-patched function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HotpatchEvent<'a> {
+    /// A `#[hotpatch(shadow)]` function returned a different output than its implementation
+    /// from the previous build.
+    ShadowDivergence {
+        /// The type name of the function.
+        name: &'a str,
+    },
+    /// A `checked` function of a new build returned different outputs than were recorded
+    /// for the same inputs, or panicked, when its recorded calls were replayed.
+    ReplayDivergence {
+        /// The type name of the function.
+        name: &'a str,
+        /// How many of the recorded calls diverged.
+        count: usize,
+    },
+    /// A `checked` or `archived` function failed to deserialize or validate its input or
+    /// output, most likely because their types changed between builds.
+    CheckedMismatch {
+        /// The type name of the function.
+        name: &'a str,
+    },
+    /// The serialized input or output of a `checked` function of a new build differs from
+    /// that of the build its callers use, detected when the new build was loaded.
+    SchemaChanged {
+        /// The type name of the function.
+        name: &'a str,
+        /// Descriptions of the changes, like "struct Player: field `hp` changed from i32 to
+        /// f32".
+        changes: &'a [String],
+        /// Whether values serialized by either build can still be deserialized by the other,
+        /// see `LIBHOTPATCH_SCHEMA_EVOLUTION`.
+        compatible: bool,
+    },
+    /// A `#[canary]` check of a function of a new build failed, panicked or crashed, and the
+    /// build was rejected.
+    CanaryFailed {
+        /// The type name of the function the canary checks.
+        name: &'a str,
+    },
+}
+
+type EventHandler = Arc<dyn Fn(&HotpatchEvent) + Send + Sync>;
+
+static EVENT_HANDLER: Persistent<RwLock<Option<EventHandler>>> =
+    Persistent::new(concat!(module_path!(), "::EVENT_HANDLER"), || {
+        RwLock::new(None)
+    });
+
+/// Sets the handler that is called with every [`HotpatchEvent`], replacing any previous one.
+///
+/// Events are also logged, the handler is meant for reacting to them programmatically. It
+/// may be called concurrently from any thread that calls a hot-patched function.
+///
+/// The handler is shared by every generation of the library, so it can be set from any
+/// build and receives the events of all of them. It is called without holding any lock, so
+/// it may set a new handler itself.
+pub fn set_event_handler(handler: impl Fn(&HotpatchEvent) + Send + Sync + 'static) {
+    *EVENT_HANDLER
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(handler));
+}
+
+pub(crate) fn report(event: HotpatchEvent) {
+    let handler = EVENT_HANDLER
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+
+    if let Some(handler) = handler {
+        handler(&event);
+    }
+}
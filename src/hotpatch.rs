@@ -1,13 +1,20 @@
 #[cfg(unix)]
 use std::ffi::c_void;
 use std::{
+    any::Any,
     cmp::Ordering,
-    fs, io, mem, ptr,
+    fmt, fs, io,
+    marker::PhantomData,
+    mem,
+    ops::{Deref, DerefMut},
+    ptr,
     sync::{
-        LazyLock,
+        LazyLock, Mutex, PoisonError,
         atomic::{AtomicPtr, AtomicU64, Ordering as AtomicOrdering, fence},
     },
 };
+#[cfg(target_os = "linux")]
+use std::{path::Path, time::Duration};
 
 use libloading::Library;
 use tempfile::TempDir;
@@ -16,9 +23,221 @@ use crate::abi::{
     boxed::{Box as AbiBox, BoxedSlice},
     str::{BoxedStr, Str},
 };
+#[cfg(target_os = "linux")]
+use crate::config::LoadFlags;
+#[cfg(feature = "checked")]
+use crate::schema;
+use crate::{
+    config::Config,
+    events::{self, HotpatchEvent},
+    os::{self, Crash},
+    recording,
+    watcher::Watcher,
+};
+
+/// Name of the export that marks a library as built against this version of libhotpatch.
+pub const ABI_MARKER: &str = concat!("__libhotpatch_abi_", env!("CARGO_PKG_VERSION"));
+
+#[linkme::distributed_slice]
+pub static HOTPATCH_FN: [HotpatchSlot] = [..];
 
 #[linkme::distributed_slice]
-pub static HOTPATCH_FN: [(AtomicPtr<()>, LibraryHandle, fn() -> (u128, &'static str))] = [..];
+pub static HOTPATCH_CANARY: [Canary] = [..];
+
+/// Serializes modifications of the [`HOTPATCH_FN`] slots.
+static SLOT_LOCK: Mutex<()> = Mutex::new(());
+
+/// The dispatch state of a `#[hotpatch]` function.
+#[repr(C)]
+pub struct HotpatchSlot {
+    fn_ptr: AtomicPtr<()>,
+    handle: LibraryHandle,
+    prev_fn_ptr: AtomicPtr<()>,
+    prev_handle: LibraryHandle,
+    type_of: fn() -> (u128, &'static str),
+    replay_fn: Option<ReplayFn>,
+    schema_fn: Option<SchemaFn>,
+    probe_fn: Option<ProbeFn>,
+}
+
+/// Calls a `checked` function with a serialized input, writing its serialized output.
+///
+/// Returns `false` if the function panicked. The panic is caught in the generation that
+/// raised it, unlike when calling the function directly.
+pub type ReplayFn = extern "C" fn(*const u8, usize, *mut BoxedSlice<u8>) -> bool;
+
+/// Describes the schema of a `checked` function in the generation it belongs to.
+///
+/// Returns an empty slice if the schema could not be traced.
+pub type SchemaFn = extern "C" fn() -> BoxedSlice<u8>;
+
+/// Checks that samples of the input (or, if the flag is set, the output) of a serialized
+/// schema deserialize in the generation the `checked` function belongs to.
+pub type ProbeFn = extern "C" fn(*const u8, usize, bool) -> bool;
+
+/// A `#[hotpatch]` static, which reads its value from the newest build.
+///
+/// Its value is registered in the function table like the implementation of a function, and
+/// the slot of the original build is updated to point to the value of each new build.
+pub struct HotConst<T> {
+    slot: &'static HotpatchSlot,
+    _marker: PhantomData<T>,
+}
+
+impl<T> HotConst<T> {
+    #[doc(hidden)]
+    pub const fn new(slot: &'static HotpatchSlot) -> Self {
+        Self {
+            slot,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Copy> HotConst<T> {
+    /// Reads the value of the newest build, polling for new builds like a call of a
+    /// `#[hotpatch]` function.
+    pub fn get(&self) -> T {
+        if let Some(watcher) = Watcher::get() {
+            watcher.poll();
+        }
+
+        let _handle = self.slot.handle();
+
+        // SAFETY: the slot points to a `T` in the generation kept loaded by the handle, since
+        // slots are only updated with values of statics of the same name and type.
+        unsafe { *self.slot.fn_ptr().cast::<T>() }
+    }
+}
+
+/// A trait object whose methods are resolved in the newest build.
+///
+/// The vtable of a `#[hotpatch]` trait impl is registered in the function table like the
+/// implementation of a function, and the value is kept where it is while the vtable is
+/// replaced by that of each new build, as long as its type keeps its name, size and
+/// alignment. Otherwise, the value keeps the vtable of the last build with its layout.
+///
+/// ```
+/// use libhotpatch::{HotDyn, hotpatch};
+///
+/// trait System {
+///     fn update(&mut self);
+/// }
+///
+/// struct Physics {
+///     steps: u32,
+/// }
+///
+/// #[hotpatch]
+/// impl System for Physics {
+///     fn update(&mut self) {
+///         self.steps += 1;
+///     }
+/// }
+///
+/// let mut system: HotDyn<dyn System> = HotDyn::new(Physics { steps: 0 });
+/// system.update();
+/// ```
+///
+/// Vtables of previous builds stay valid, since builds are never unloaded.
+pub struct HotDyn<D: ?Sized> {
+    data: *mut (),
+    slot: &'static HotpatchSlot,
+    _marker: PhantomData<Box<D>>,
+}
+
+/// A type with a `#[hotpatch]` impl of the trait `D`.
+///
+/// # Safety
+///
+/// The slot must point to a `fn(*mut ()) -> *mut D` that casts a pointer to `Self`.
+pub unsafe trait HotImpl<D: ?Sized>: Sized {
+    #[doc(hidden)]
+    fn slot() -> &'static HotpatchSlot;
+}
+
+impl<D: ?Sized> HotDyn<D> {
+    pub fn new<T: HotImpl<D>>(value: T) -> Self {
+        let slot = T::slot();
+        let (hash, _) = (slot.type_of)();
+
+        // Slots of the original build are the only ones that are updated.
+        let slot = Watcher::get()
+            .and_then(|watcher| watcher.find_slot(hash))
+            .unwrap_or(slot);
+
+        Self {
+            data: AbiBox::new(value).into_raw().cast(),
+            slot,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Resolves the trait object with the vtable of the newest build, polling for new builds
+    /// like a call of a `#[hotpatch]` function.
+    fn resolve(&self) -> *mut D {
+        if let Some(watcher) = Watcher::get() {
+            watcher.poll();
+        }
+
+        self.resolve_loaded()
+    }
+
+    /// Resolves the trait object with the vtable of the newest build that is already loaded.
+    fn resolve_loaded(&self) -> *mut D {
+        // SAFETY: the slot points to a cast of the type of the value, see `HotImpl`, since
+        // slots are only updated with casts of types of the same name and layout.
+        let cast = unsafe { mem::transmute::<*mut (), fn(*mut ()) -> *mut D>(self.slot.fn_ptr()) };
+        cast(self.data)
+    }
+}
+
+impl<D: ?Sized> Deref for HotDyn<D> {
+    type Target = D;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: the value is owned, and valid until it is dropped.
+        unsafe { &*self.resolve() }
+    }
+}
+
+impl<D: ?Sized> DerefMut for HotDyn<D> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: the value is owned, and valid until it is dropped.
+        unsafe { &mut *self.resolve() }
+    }
+}
+
+impl<D: ?Sized> Drop for HotDyn<D> {
+    fn drop(&mut self) {
+        // SAFETY: the value was allocated by `new`, and is dropped by the newest loaded build
+        // that has its layout. Dropping does not poll, so it never waits for a build to load.
+        unsafe {
+            ptr::drop_in_place(self.resolve_loaded());
+            os::free(self.data.cast());
+        }
+    }
+}
+
+unsafe impl<D: ?Sized + Send> Send for HotDyn<D> {}
+
+unsafe impl<D: ?Sized + Sync> Sync for HotDyn<D> {}
+
+/// A `#[canary]` check of a hot-patch function.
+#[repr(C)]
+pub struct Canary {
+    check: extern "C" fn() -> bool,
+    target_name: fn() -> &'static str,
+}
+
+/// Which functions are reverted when a `#[hotpatch(fallback_on_panic)]` function panics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FallbackScope {
+    /// Only the function that panicked.
+    Function,
+    /// Every function patched from the same generation as the function that panicked.
+    Generation,
+}
 
 #[repr(C)]
 pub struct LibraryHandle {
@@ -34,7 +253,217 @@ struct LibraryPayload {
     #[cfg(windows)]
     lib_handle: isize,
 
-    temp_path: BoxedStr,
+    temp_path: Option<BoxedStr>,
+
+    text_start: usize,
+    text_end: usize,
+}
+
+impl HotpatchSlot {
+    pub const fn new(fn_ptr: *mut (), type_of: fn() -> (u128, &'static str)) -> Self {
+        Self {
+            fn_ptr: AtomicPtr::new(fn_ptr),
+            handle: LibraryHandle::null(),
+            prev_fn_ptr: AtomicPtr::new(ptr::null_mut()),
+            prev_handle: LibraryHandle::null(),
+            type_of,
+            replay_fn: None,
+            schema_fn: None,
+            probe_fn: None,
+        }
+    }
+
+    /// Creates the slot of a `checked` function, whose recorded calls can be replayed and
+    /// whose schema is compared between builds.
+    pub const fn new_checked(
+        fn_ptr: *mut (),
+        type_of: fn() -> (u128, &'static str),
+        replay_fn: ReplayFn,
+        schema_fn: SchemaFn,
+        probe_fn: ProbeFn,
+    ) -> Self {
+        Self {
+            fn_ptr: AtomicPtr::new(fn_ptr),
+            handle: LibraryHandle::null(),
+            prev_fn_ptr: AtomicPtr::new(ptr::null_mut()),
+            prev_handle: LibraryHandle::null(),
+            type_of,
+            replay_fn: Some(replay_fn),
+            schema_fn: Some(schema_fn),
+            probe_fn: Some(probe_fn),
+        }
+    }
+
+    /// The current implementation of the function.
+    #[inline]
+    pub fn fn_ptr(&self) -> *mut () {
+        self.fn_ptr.load(AtomicOrdering::Relaxed)
+    }
+
+    /// A handle that keeps the generation of the current implementation loaded.
+    #[inline]
+    pub fn handle(&self) -> LibraryHandle {
+        self.handle.clone()
+    }
+
+    /// The implementation of the function from before the last patch.
+    ///
+    /// Unlike [`HotpatchSlot::handle`], no handle to its generation is returned, since the
+    /// previous handle is dropped by the next patch while it could be cloned. Builds are never
+    /// unloaded, so the implementation stays callable without one.
+    #[inline]
+    pub fn previous(&self) -> Option<*mut ()> {
+        let prev_fn_ptr = self.prev_fn_ptr.load(AtomicOrdering::Relaxed);
+        (!prev_fn_ptr.is_null()).then_some(prev_fn_ptr)
+    }
+
+    /// Compares the outputs of the current and previous implementation of a
+    /// `#[hotpatch(shadow)]` function, reporting if they diverge.
+    pub fn report_shadow<T: PartialEq + ?Sized>(&self, current: &T, previous: &T) {
+        if current == previous {
+            return;
+        }
+
+        let (_, name) = (self.type_of)();
+        log::warn!("{name} diverged from the implementation of the previous generation");
+
+        events::report(HotpatchEvent::ShadowDivergence { name });
+    }
+
+    /// Records a call of a `checked` function, if recording is enabled.
+    pub fn record(&self, input: &[u8], output: &[u8]) {
+        if !Config::get().record {
+            return;
+        }
+
+        let (hash, name) = (self.type_of)();
+
+        if let Err(e) = recording::record(hash, name, input, output) {
+            log::warn!("error recording a call of {name}: {e}");
+        }
+    }
+
+    /// Reports that a `checked` or `archived` function failed to deserialize or validate its
+    /// input or output.
+    pub fn report_mismatch(&self) {
+        let (_, name) = (self.type_of)();
+        log::error!("{name} failed to deserialize or validate its input or output");
+
+        events::report(HotpatchEvent::CheckedMismatch { name });
+    }
+
+    /// Reverts a `checked` or `archived` function after it failed to deserialize or validate
+    /// its input or output, if it still points to `failed_fn_ptr`.
+    ///
+    /// Returns whether the call can be retried, like [`HotpatchSlot::revert`].
+    pub fn revert_mismatched(&self, failed_fn_ptr: *mut ()) -> bool {
+        let _lock = SLOT_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if self.fn_ptr() == failed_fn_ptr {
+            self.revert_locked();
+        }
+
+        self.fn_ptr() != failed_fn_ptr
+    }
+
+    /// Reverts the function to its implementation from before the last patch, if it still
+    /// points to `failed_fn_ptr`.
+    ///
+    /// Returns whether the function no longer points to `failed_fn_ptr`, and the call can be
+    /// retried with [`HotpatchSlot::fn_ptr`].
+    pub fn revert(
+        &self,
+        failed_fn_ptr: *mut (),
+        scope: FallbackScope,
+        payload: &(dyn Any + Send),
+    ) -> bool {
+        let msg = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("Box<dyn Any>");
+
+        self.revert_failed(failed_fn_ptr, scope, format_args!("panicked ({msg})"))
+    }
+
+    /// Reverts every function patched from the same generation as the function that
+    /// crashed, if it still points to `failed_fn_ptr`.
+    ///
+    /// The crashed call was abandoned midway, which may leave any state of the generation
+    /// inconsistent, so none of its functions are called again. Returns whether the call can
+    /// be retried, like [`HotpatchSlot::revert`].
+    pub fn revert_crashed(&self, failed_fn_ptr: *mut (), crash: &Crash) -> bool {
+        self.revert_failed(
+            failed_fn_ptr,
+            FallbackScope::Generation,
+            format_args!("crashed ({crash})"),
+        )
+    }
+
+    fn revert_failed(
+        &self,
+        failed_fn_ptr: *mut (),
+        scope: FallbackScope,
+        reason: fmt::Arguments,
+    ) -> bool {
+        let _lock = SLOT_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if self.fn_ptr() == failed_fn_ptr {
+            let (_, name) = (self.type_of)();
+
+            log::error!("{name} {reason}, reverting to the previous generation");
+
+            match scope {
+                FallbackScope::Function => self.revert_locked(),
+                FallbackScope::Generation => {
+                    let failed_handle = self.handle();
+
+                    for slot in HOTPATCH_FN.iter() {
+                        if slot.handle.ptr_eq(&failed_handle) {
+                            slot.revert_locked();
+                        }
+                    }
+                }
+            }
+        }
+
+        self.fn_ptr() != failed_fn_ptr
+    }
+
+    fn update(&self, fn_ptr: *mut (), handle: LibraryHandle) {
+        let _lock = SLOT_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let prev_fn_ptr = self.fn_ptr.swap(fn_ptr, AtomicOrdering::Relaxed);
+        let prev_handle = self.handle.replace(handle);
+
+        self.prev_fn_ptr.store(prev_fn_ptr, AtomicOrdering::Relaxed);
+        let _ = self.prev_handle.replace(prev_handle);
+    }
+
+    fn revert_locked(&self) {
+        let prev_fn_ptr = self
+            .prev_fn_ptr
+            .swap(ptr::null_mut(), AtomicOrdering::Relaxed);
+
+        if prev_fn_ptr.is_null() {
+            return;
+        }
+
+        let (_, name) = (self.type_of)();
+        log::debug!("reverting {name}");
+
+        self.fn_ptr.store(prev_fn_ptr, AtomicOrdering::Relaxed);
+
+        let prev_handle = self.prev_handle.replace(LibraryHandle::null());
+        let _ = self.handle.replace(prev_handle);
+    }
+}
+
+impl Canary {
+    /// `check` must catch panics of the check, and return `false` instead.
+    pub const fn new(check: extern "C" fn() -> bool, target_name: fn() -> &'static str) -> Self {
+        Self { check, target_name }
+    }
 }
 
 impl LibraryHandle {
@@ -44,6 +473,26 @@ impl LibraryHandle {
         }
     }
 
+    /// Calls `f`, catching faults raised by the code of this generation.
+    ///
+    /// `f` is called unguarded if the handle is null, or faults can not be caught on this
+    /// platform.
+    pub fn guard_crashes<F: FnOnce() -> R, R>(&self, f: F) -> Result<R, Crash> {
+        let payload_ptr = self.ptr.load(AtomicOrdering::Relaxed);
+
+        // SAFETY: pointer is either null or points to an initialized `LibraryPayload`,
+        // since there is an open handle to it (self).
+        let text = unsafe { payload_ptr.as_ref() }
+            .map_or(0..0, |payload| payload.text_start..payload.text_end);
+
+        os::guard_crashes(text, f)
+    }
+
+    fn ptr_eq(&self, other: &Self) -> bool {
+        let ptr = self.ptr.load(AtomicOrdering::Relaxed);
+        !ptr.is_null() && ptr == other.ptr.load(AtomicOrdering::Relaxed)
+    }
+
     fn replace(&self, mut new: Self) -> Self {
         let new_ptr = mem::replace(&mut new.ptr, AtomicPtr::new(ptr::null_mut())).into_inner();
         let old_ptr = self.ptr.swap(new_ptr, AtomicOrdering::Relaxed);
@@ -55,7 +504,10 @@ impl LibraryHandle {
 }
 
 impl LibraryPayload {
-    pub fn make_handle(lib: Library, dir: TempDir) -> LibraryHandle {
+    pub fn make_handle(lib: Library, dir: Option<TempDir>) -> LibraryHandle {
+        let text = unsafe { lib.get::<extern "C" fn()>(b"__libhotpatch_fn_table") }
+            .map_or(0..0, |symbol| os::executable_range(*symbol as *const _));
+
         let payload = AbiBox::new(Self {
             refcount: AtomicU64::new(1),
 
@@ -64,7 +516,10 @@ impl LibraryPayload {
             #[cfg(windows)]
             lib_handle: libloading::os::windows::Library::from(lib).into_raw(),
 
-            temp_path: BoxedStr::new(dir.path().to_string_lossy()),
+            temp_path: dir.map(|dir| BoxedStr::new(dir.path().to_string_lossy())),
+
+            text_start: text.start,
+            text_end: text.end,
         });
 
         LibraryHandle {
@@ -132,28 +587,25 @@ impl Drop for LibraryPayload {
             #[cfg(windows)]
             let _ = libloading::os::windows::Library::from_raw(self.lib_handle);
         }
-        let _ = fs::remove_dir(&*self.temp_path);
+        if let Some(temp_path) = &self.temp_path {
+            let _ = fs::remove_dir_all(&**temp_path);
+        }
     }
 }
 
 #[repr(C)]
 #[derive(Clone, Copy)]
 struct HotpatchFn {
-    fn_ptr: &'static AtomicPtr<()>,
-    handle: &'static LibraryHandle,
+    slot: &'static HotpatchSlot,
     hash: u128,
     name: Str<'static>,
 }
 
-pub fn update_fn_table(hotpatch_library: Library, dir: TempDir) -> io::Result<()> {
-    static CACHED_HOTPATCH_FN: LazyLock<BoxedSlice<HotpatchFn>> = LazyLock::new(build_fn_table);
+/// The function table of this generation.
+static CACHED_HOTPATCH_FN: LazyLock<BoxedSlice<HotpatchFn>> = LazyLock::new(build_fn_table);
 
-    let fn_table = unsafe {
-        hotpatch_library
-            .get::<extern "C" fn() -> BoxedSlice<HotpatchFn>>(b"__libhotpatch_fn_table")
-            .map(|getter| getter())
-            .map_err(io::Error::other)
-    };
+pub fn update_fn_table(hotpatch_library: Library, dir: Option<TempDir>) -> io::Result<()> {
+    let fn_table = load_fn_table(&hotpatch_library);
 
     let handle = LibraryPayload::make_handle(hotpatch_library, dir);
     let fn_table = fn_table?;
@@ -179,10 +631,7 @@ pub fn update_fn_table(hotpatch_library: Library, dir: TempDir) -> io::Result<()
                 let _ = my_fns.next();
                 let _ = new_fns.next();
 
-                let new_ptr = new_fn.fn_ptr.load(AtomicOrdering::Relaxed);
-                my_fn.fn_ptr.store(new_ptr, AtomicOrdering::Relaxed);
-
-                let _ = my_fn.handle.replace(handle.clone());
+                my_fn.slot.update(new_fn.slot.fn_ptr(), handle.clone());
             }
         }
     }
@@ -198,26 +647,313 @@ pub fn update_fn_table(hotpatch_library: Library, dir: TempDir) -> io::Result<()
     Ok(())
 }
 
+/// Loads the library at `path` in a child process and returns the hash and name of every
+/// function in its function table.
+///
+/// Like [`os::run_in_child`], the outer result fails if the child could not be run or timed
+/// out, and the inner one if the library failed to load in it.
+#[cfg(target_os = "linux")]
+pub fn validate_fn_table(
+    path: &Path,
+    flags: LoadFlags,
+    timeout: Duration,
+) -> io::Result<io::Result<Vec<(u128, String)>>> {
+    let output = os::run_in_child(timeout, || {
+        let hotpatch_library = os::load_library(path, flags)?;
+        let fn_table = load_fn_table(&hotpatch_library)?;
+
+        let mut output = vec![];
+
+        for hotpatch_fn in fn_table.iter() {
+            let name = hotpatch_fn.name.as_str();
+
+            output.extend_from_slice(&hotpatch_fn.hash.to_le_bytes());
+            output.extend_from_slice(&(name.len() as u64).to_le_bytes());
+            output.extend_from_slice(name.as_bytes());
+        }
+
+        Ok(output)
+    })?;
+
+    let output = match output {
+        Ok(output) => output,
+        Err(e) => return Ok(Err(e)),
+    };
+
+    let mut fns = vec![];
+    let mut output = output.as_slice();
+
+    while let Some((hash, rest)) = output.split_first_chunk::<16>()
+        && let Some((len, rest)) = rest.split_first_chunk::<8>()
+        && let Some((name, rest)) = rest.split_at_checked(u64::from_le_bytes(*len) as usize)
+    {
+        fns.push((
+            u128::from_le_bytes(*hash),
+            String::from_utf8_lossy(name).into_owned(),
+        ));
+
+        output = rest;
+    }
+
+    if !output.is_empty() {
+        return Ok(Err(io::Error::other("malformed function table")));
+    }
+
+    Ok(Ok(fns))
+}
+
+/// Replays the recorded calls of every `checked` function of a newly loaded library, and
+/// returns the names of the functions whose outputs diverged from the recorded ones.
+pub fn replay_fn_table(hotpatch_library: &Library) -> io::Result<Vec<String>> {
+    let fn_table = load_fn_table(hotpatch_library)?;
+
+    let mut diverged = vec![];
+
+    for hotpatch_fn in fn_table.iter() {
+        let Some(replay_fn) = hotpatch_fn.slot.replay_fn else {
+            continue;
+        };
+
+        let name = hotpatch_fn.name.as_str();
+        let calls = recording::read(hotpatch_fn.hash, name)?;
+
+        let count = calls
+            .iter()
+            .filter(|call| {
+                let mut output = mem::MaybeUninit::<BoxedSlice<u8>>::uninit();
+
+                if !replay_fn(call.input.as_ptr(), call.input.len(), output.as_mut_ptr()) {
+                    return true;
+                }
+
+                // SAFETY: `replay_fn` writes the output if it returns `true`.
+                let output = unsafe { output.assume_init() };
+                *output != *call.output
+            })
+            .count();
+
+        if count == 0 {
+            if !calls.is_empty() {
+                log::debug!("{name} matched {} recorded calls", calls.len());
+            }
+            continue;
+        }
+
+        log::warn!(
+            "{name} diverged from {count} of {} recorded calls",
+            calls.len()
+        );
+
+        events::report(HotpatchEvent::ReplayDivergence { name, count });
+        diverged.push(name.to_owned());
+    }
+
+    Ok(diverged)
+}
+
+/// Compares the schemas of the `checked` functions of a newly loaded library with those of
+/// this generation, whose types the callers of the functions use, and returns the names of
+/// the functions whose schemas changed, with whether all of their changes are compatible.
+#[cfg(feature = "checked")]
+pub fn diff_schemas(hotpatch_library: &Library) -> io::Result<Vec<(String, bool)>> {
+    let fn_table = load_fn_table(hotpatch_library)?;
+
+    let mut changed = vec![];
+
+    for new_fn in fn_table.iter() {
+        let Ok(i) = CACHED_HOTPATCH_FN.binary_search_by_key(&new_fn.hash, |my_fn| my_fn.hash)
+        else {
+            continue;
+        };
+
+        let my_slot = CACHED_HOTPATCH_FN[i].slot;
+        let new_slot = new_fn.slot;
+
+        let (Some(my_schema_fn), Some(new_schema_fn)) = (my_slot.schema_fn, new_slot.schema_fn)
+        else {
+            continue;
+        };
+
+        let name = new_fn.name.as_str();
+
+        let (my_bytes, new_bytes) = (my_schema_fn(), new_schema_fn());
+
+        let (Some(my_schema), Some(new_schema)) =
+            (schema::read(&my_bytes), schema::read(&new_bytes))
+        else {
+            log::debug!("skipping the schema of {name}, it could not be traced");
+            continue;
+        };
+
+        let mut changes = schema::diff(&my_schema, &new_schema);
+
+        if changes.is_empty() {
+            continue;
+        }
+
+        // Whether added and removed fields have defaults only shows when deserializing.
+        if changes.iter().all(|change| change.compatible)
+            && let (Some(my_probe_fn), Some(new_probe_fn)) = (my_slot.probe_fn, new_slot.probe_fn)
+        {
+            let probes = [
+                (
+                    new_probe_fn,
+                    &new_bytes,
+                    &my_bytes,
+                    false,
+                    "arguments of callers can not be deserialized by the new build",
+                ),
+                (
+                    my_probe_fn,
+                    &my_bytes,
+                    &new_bytes,
+                    true,
+                    "outputs of the new build can not be deserialized by callers",
+                ),
+            ];
+
+            for (probe_fn, own, other, output, description) in probes {
+                // Samples of its own schema fail when a type rejects the values of samples.
+                if !probe_fn(own.as_ptr(), own.len(), output) {
+                    log::debug!("skipping a probe of the schema of {name}, it rejects samples");
+                    continue;
+                }
+
+                if !probe_fn(other.as_ptr(), other.len(), output) {
+                    changes.push(schema::Change {
+                        description: description.to_owned(),
+                        compatible: false,
+                    });
+                }
+            }
+        }
+
+        let compatible = changes.iter().all(|change| change.compatible);
+
+        for change in &changes {
+            if change.compatible {
+                log::info!("{name} schema changed: {}", change.description);
+            } else {
+                log::warn!("{name} schema changed incompatibly: {}", change.description);
+            }
+        }
+
+        let descriptions: Vec<_> = changes
+            .into_iter()
+            .map(|change| change.description)
+            .collect();
+
+        events::report(HotpatchEvent::SchemaChanged {
+            name,
+            changes: &descriptions,
+            compatible,
+        });
+        changed.push((name.to_owned(), compatible));
+    }
+
+    Ok(changed)
+}
+
+/// Runs the canary checks of a newly loaded library, failing if any of them fail.
+///
+/// Faults raised by the code of the library are caught like with [`LibraryHandle::guard_crashes`],
+/// and fail the check.
+pub fn run_canaries(hotpatch_library: &Library) -> io::Result<()> {
+    let canaries = unsafe {
+        hotpatch_library
+            .get::<extern "C" fn() -> BoxedSlice<CanaryFn>>(b"__libhotpatch_canary_table")
+            .map(|getter| getter())
+            .map_err(io::Error::other)?
+    };
+
+    let mut failed = vec![];
+
+    for canary in canaries.iter() {
+        let name = canary.target_name.as_str();
+        let text = os::executable_range(canary.check as *const _);
+
+        match os::guard_crashes(text, || (canary.check)()) {
+            Ok(true) => {
+                log::debug!("canary of {name} passed");
+                continue;
+            }
+            Ok(false) => log::error!("canary of {name} failed"),
+            Err(crash) => log::error!("canary of {name} crashed ({crash})"),
+        }
+
+        events::report(HotpatchEvent::CanaryFailed { name });
+        failed.push(name);
+    }
+
+    if !failed.is_empty() {
+        return Err(io::Error::other(format!(
+            "canary checks failed for {}",
+            failed.join(", ")
+        )));
+    }
+
+    Ok(())
+}
+
+fn load_fn_table(hotpatch_library: &Library) -> io::Result<BoxedSlice<HotpatchFn>> {
+    unsafe {
+        hotpatch_library
+            .get::<extern "C" fn() -> BoxedSlice<HotpatchFn>>(b"__libhotpatch_fn_table")
+            .map(|getter| getter())
+            .map_err(io::Error::other)
+    }
+}
+
 fn build_fn_table() -> BoxedSlice<HotpatchFn> {
     let mut hotpatch_fns = HOTPATCH_FN
         .iter()
-        .map(|(fn_ptr, handle, type_of)| {
-            let (hash, name) = type_of();
+        .map(|slot| {
+            let (hash, name) = (slot.type_of)();
             HotpatchFn {
-                fn_ptr,
-                handle,
+                slot,
                 hash,
                 name: Str::new(name),
             }
         })
         .collect::<Vec<_>>();
 
-    hotpatch_fns.sort_by(|a, b| a.hash.cmp(&b.hash));
+    hotpatch_fns.sort_by_key(|f| f.hash);
 
     BoxedSlice::new(&hotpatch_fns)
 }
 
+/// Finds the slot of this generation whose function hashes to `hash`, or returns null.
+pub extern "C" fn find_slot(hash: u128) -> *const HotpatchSlot {
+    CACHED_HOTPATCH_FN
+        .binary_search_by_key(&hash, |f| f.hash)
+        .map_or(ptr::null(), |i| CACHED_HOTPATCH_FN[i].slot)
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct CanaryFn {
+    check: extern "C" fn() -> bool,
+    target_name: Str<'static>,
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn __libhotpatch_canary_table() -> BoxedSlice<CanaryFn> {
+    let canaries = HOTPATCH_CANARY
+        .iter()
+        .map(|canary| CanaryFn {
+            check: canary.check,
+            target_name: Str::new((canary.target_name)()),
+        })
+        .collect::<Vec<_>>();
+
+    BoxedSlice::new(&canaries)
+}
+
 #[unsafe(no_mangle)]
 extern "C" fn __libhotpatch_fn_table() -> BoxedSlice<HotpatchFn> {
     build_fn_table()
 }
+
+#[used]
+#[unsafe(export_name = concat!("__libhotpatch_abi_", env!("CARGO_PKG_VERSION")))]
+static __LIBHOTPATCH_ABI: u8 = 0;
@@ -1,15 +1,36 @@
 #![doc = include_str!("../README.md")]
 
 mod abi;
+#[cfg(feature = "archived")]
+pub mod archived;
+#[cfg(feature = "checked")]
+pub mod codec;
+mod config;
+mod events;
 mod hotpatch;
 mod lock;
 mod os;
+mod persistent;
+mod recording;
+#[cfg(feature = "checked")]
+mod schema;
+mod scratch;
+pub mod sync;
+mod thread_local;
 mod watcher;
 
 // Crate proc macro reexports:
 #[doc(hidden)]
+pub use hotpatch::Canary;
+#[doc(hidden)]
+pub use hotpatch::FallbackScope;
+#[doc(hidden)]
+pub use hotpatch::HOTPATCH_CANARY;
+#[doc(hidden)]
 pub use hotpatch::HOTPATCH_FN;
 #[doc(hidden)]
+pub use hotpatch::HotpatchSlot;
+#[doc(hidden)]
 pub use hotpatch::LibraryHandle;
 #[doc(hidden)]
 pub use watcher::Watcher;
@@ -22,15 +43,22 @@ pub use linkme;
 #[doc(hidden)]
 pub use linkme::distributed_slice;
 
-// Proc macro reexports for `rmp-serde`:
+// Proc macro reexports for `checked` and `archived`:
+#[cfg(any(feature = "checked", feature = "archived"))]
+#[doc(hidden)]
+pub use abi::boxed::BoxedSlice;
 #[cfg(feature = "checked")]
 #[doc(hidden)]
-pub use rmp_serde;
+pub use abi::buffer::{CheckedBuffers, OutputBuffer};
 #[cfg(feature = "checked")]
 #[doc(hidden)]
-pub use abi::boxed::BoxedSlice;
+pub use schema::{describe_schema, probe_schema};
 
-pub use libhotpatch_macros::hotpatch;
+pub use events::{HotpatchEvent, set_event_handler};
+pub use hotpatch::{HotConst, HotDyn, HotImpl};
+pub use libhotpatch_macros::{canary, hotpatch};
+pub use persistent::Persistent;
+pub use thread_local::HotThreadLocal;
 
 pub(crate) static TARGET_DIR: &str = env!("LIBHOTPATCH_TARGET_DIR");
 
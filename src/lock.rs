@@ -4,7 +4,7 @@ use std::{
     io,
 };
 
-use crate::TARGET_DIR;
+use crate::scratch::scratch_path;
 
 pub struct HotpatchLock(File);
 
@@ -30,5 +30,5 @@ impl Drop for HotpatchLock {
 
 fn hotpatch_lock_path() -> String {
     let pid = std::process::id();
-    format!("{TARGET_DIR}/.hotpatch/{pid}.lock")
+    format!("{}/{pid}.lock", scratch_path())
 }
@@ -1,19 +1,162 @@
 use std::{
-    ffi::{OsStr, OsString},
+    ffi::{OsStr, OsString, c_void},
+    fmt, io,
+    ops::Range,
     path::{Path, PathBuf},
 };
 
 use libloading::Library;
 
+use crate::config::LoadFlags;
+
+#[cfg(all(unix, not(target_vendor = "apple")))]
+mod elf;
+#[cfg(target_os = "linux")]
+mod linux;
 #[cfg(unix)]
 mod unix;
 #[cfg(windows)]
 mod windows;
 
+#[cfg(target_os = "linux")]
+pub use linux::{create_memfd, run_in_child};
 #[cfg(unix)]
-pub use unix::{aligned_alloc, free};
+pub use unix::{aligned_alloc, free, process_exists};
 #[cfg(windows)]
-pub use windows::{aligned_alloc, free};
+pub use windows::{aligned_alloc, free, process_exists};
+
+/// A fault raised by code called through [`guard_crashes`].
+#[derive(Clone, Copy, Debug)]
+pub struct Crash {
+    pub signal: &'static str,
+    pub pc: usize,
+    pub fault_addr: usize,
+}
+
+/// Loads a hot-patched build of a shared library.
+///
+/// `flags` are ignored on platforms other than Unix.
+pub fn load_library(path: &Path, flags: LoadFlags) -> io::Result<Library> {
+    #[cfg(unix)]
+    let lib = unsafe {
+        libloading::os::unix::Library::open(Some(path), unix::dlopen_flags(flags))
+            .map(Library::from)
+    };
+
+    #[cfg(not(unix))]
+    let lib = {
+        let _ = flags;
+        unsafe { Library::new(path) }
+    };
+
+    lib.map_err(io::Error::other)
+}
+
+/// Checks that a library image is complete before it is loaded.
+///
+/// Only ELF images are validated, other formats are accepted as is.
+pub fn validate_library(bytes: &[u8]) -> io::Result<()> {
+    #[cfg(all(unix, not(target_vendor = "apple")))]
+    elf::Elf::parse(bytes)?;
+
+    #[cfg(not(all(unix, not(target_vendor = "apple"))))]
+    let _ = bytes;
+
+    Ok(())
+}
+
+/// Inspects a library image before it is loaded, without running any of its code.
+///
+/// Checks that all of `exports` are defined, and that its undefined symbols can be resolved
+/// in the global scope of the current process or in the libraries it needs, which may have
+/// been loaded locally. Only ELF images are inspected, other formats are accepted as is.
+pub fn inspect_library(bytes: &[u8], exports: &[&str]) -> io::Result<()> {
+    #[cfg(all(unix, not(target_vendor = "apple")))]
+    {
+        let elf = elf::Elf::parse(bytes)?;
+        let symbols = elf.dynamic_symbols()?;
+
+        for export in exports {
+            if !symbols
+                .iter()
+                .any(|symbol| symbol.is_defined && symbol.name.to_bytes() == export.as_bytes())
+            {
+                return Err(io::Error::other(format!("missing export {export}")));
+            }
+        }
+
+        let needed = elf.needed_libraries()?;
+
+        let unloaded = needed
+            .iter()
+            .filter(|needed| !unix::is_library_loaded(needed))
+            .collect::<Vec<_>>();
+
+        // Symbols may come from libraries that `dlopen` would load first.
+        if !unloaded.is_empty() {
+            log::debug!("not checking undefined symbols, {unloaded:?} are not loaded");
+            return Ok(());
+        }
+
+        let unresolved = symbols
+            .iter()
+            .filter(|symbol| !symbol.is_defined && !symbol.is_weak)
+            .filter(|symbol| !unix::is_symbol_loaded(symbol.name))
+            .filter(|symbol| {
+                !needed
+                    .iter()
+                    .any(|needed| unix::is_symbol_in_library(needed, symbol.name))
+            })
+            .map(|symbol| symbol.name.to_string_lossy())
+            .collect::<Vec<_>>();
+
+        if !unresolved.is_empty() {
+            return Err(io::Error::other(format!(
+                "unresolved symbols: {}",
+                unresolved.join(", ")
+            )));
+        }
+    }
+
+    #[cfg(not(all(unix, not(target_vendor = "apple"))))]
+    let _ = (bytes, exports);
+
+    Ok(())
+}
+
+/// Calls `f`, catching faults (`SIGSEGV` and `SIGBUS`) raised by code in `text`.
+///
+/// A caught fault abandons the call without running any destructors. Faults are only
+/// caught on Linux x86-64 and AArch64, `f` is called unguarded on other platforms.
+pub fn guard_crashes<F: FnOnce() -> R, R>(text: Range<usize>, f: F) -> Result<R, Crash> {
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    if !text.is_empty() {
+        return linux::crash::guard(text, f);
+    }
+
+    let _ = text;
+    Ok(f())
+}
+
+/// Returns the address range spanned by the executable segments of the loaded object that
+/// contains `addr`.
+///
+/// Always returns an empty range if [`guard_crashes`] is not supported on this platform.
+pub fn executable_range(addr: *const c_void) -> Range<usize> {
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    if let Some(range) = linux::crash::executable_range(addr) {
+        return range;
+    }
+
+    let _ = addr;
+    0..0
+}
 
 #[derive(Debug)]
 pub struct Module {
@@ -46,3 +189,13 @@ impl Module {
         unsafe { Library::new(self.file_path()) }
     }
 }
+
+impl fmt::Display for Crash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at {:#x} accessing {:#x}",
+            self.signal, self.pc, self.fault_addr
+        )
+    }
+}
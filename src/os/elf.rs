@@ -0,0 +1,374 @@
+use std::{ffi::CStr, io, ops::Range};
+
+const EI_CLASS: usize = 4;
+const EI_DATA: usize = 5;
+
+const ELFCLASS32: u8 = 1;
+const ELFCLASS64: u8 = 2;
+
+const ELFDATA2LSB: u8 = 1;
+const ELFDATA2MSB: u8 = 2;
+
+const ET_DYN: u16 = 3;
+
+const SHT_DYNAMIC: u32 = 6;
+const SHT_NOBITS: u32 = 8;
+const SHT_DYNSYM: u32 = 11;
+
+const SHN_UNDEF: u16 = 0;
+
+const STB_WEAK: u8 = 2;
+
+const DT_NULL: u64 = 0;
+const DT_NEEDED: u64 = 1;
+
+/// A parsed and bounds-checked ELF shared object image.
+pub struct Elf<'a> {
+    bytes: &'a [u8],
+    is_64: bool,
+    is_le: bool,
+    sections: Vec<SectionHeader>,
+}
+
+/// An entry of the dynamic symbol table.
+#[derive(Clone, Copy, Debug)]
+pub struct DynamicSymbol<'a> {
+    pub name: &'a CStr,
+    pub is_defined: bool,
+    pub is_weak: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct SectionHeader {
+    kind: u32,
+    offset: u64,
+    size: u64,
+    link: u32,
+}
+
+impl<'a> Elf<'a> {
+    /// Parses the ELF header and section header table of `bytes`.
+    ///
+    /// Fails if the image is not a shared object for the current target, or if any of the
+    /// tables or sections lie outside of it, as is the case for truncated linker output.
+    pub fn parse(bytes: &'a [u8]) -> io::Result<Self> {
+        if !bytes.starts_with(b"\x7fELF") {
+            return Err(invalid_data("missing ELF magic"));
+        }
+
+        let is_64 = match bytes.get(EI_CLASS) {
+            Some(&ELFCLASS32) => false,
+            Some(&ELFCLASS64) => true,
+            _ => return Err(invalid_data("unknown ELF class")),
+        };
+
+        let is_le = match bytes.get(EI_DATA) {
+            Some(&ELFDATA2LSB) => true,
+            Some(&ELFDATA2MSB) => false,
+            _ => return Err(invalid_data("unknown ELF data encoding")),
+        };
+
+        if is_64 != cfg!(target_pointer_width = "64") || is_le != cfg!(target_endian = "little") {
+            return Err(invalid_data(
+                "ELF class or data encoding do not match the target",
+            ));
+        }
+
+        let mut elf = Self {
+            bytes,
+            is_64,
+            is_le,
+            sections: vec![],
+        };
+
+        if elf.u16_at(16)? != ET_DYN {
+            return Err(invalid_data("not an ELF shared object"));
+        }
+
+        let (shoff, shentsize, shnum) = if is_64 {
+            (elf.u64_at(0x28)?, elf.u16_at(0x3a)?, elf.u16_at(0x3c)?)
+        } else {
+            (
+                elf.u32_at(0x20)? as u64,
+                elf.u16_at(0x2e)?,
+                elf.u16_at(0x30)?,
+            )
+        };
+
+        if shoff == 0 || shnum == 0 {
+            return Err(invalid_data("missing section header table"));
+        }
+
+        if shentsize != if is_64 { 64 } else { 40 } {
+            return Err(invalid_data("unexpected section header size"));
+        }
+
+        elf.range(shoff, shnum as u64 * shentsize as u64)?;
+
+        let sections = (0..shnum as u64)
+            .map(|i| elf.section_header(shoff + i * shentsize as u64))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        for section in &sections {
+            if section.kind != SHT_NOBITS {
+                elf.range(section.offset, section.size)?;
+            }
+        }
+
+        elf.sections = sections;
+
+        Ok(elf)
+    }
+
+    /// Returns the named entries of the dynamic symbol table.
+    pub fn dynamic_symbols(&self) -> io::Result<Vec<DynamicSymbol<'a>>> {
+        let Some(dynsym) = self.section_of_kind(SHT_DYNSYM) else {
+            return Ok(vec![]);
+        };
+
+        let strtab = self.linked_section_data(dynsym)?;
+        let entsize = if self.is_64 { 24 } else { 16 };
+
+        let mut symbols = vec![];
+
+        // The first entry is always the null symbol.
+        for i in 1..dynsym.size / entsize {
+            let offset = dynsym.offset + i * entsize;
+
+            let (name, info, shndx) = if self.is_64 {
+                (
+                    self.u32_at(offset)?,
+                    self.u8_at(offset + 0x04)?,
+                    self.u16_at(offset + 0x06)?,
+                )
+            } else {
+                (
+                    self.u32_at(offset)?,
+                    self.u8_at(offset + 0x0c)?,
+                    self.u16_at(offset + 0x0e)?,
+                )
+            };
+
+            let name = str_at(strtab, name)?;
+
+            if name.is_empty() {
+                continue;
+            }
+
+            symbols.push(DynamicSymbol {
+                name,
+                is_defined: shndx != SHN_UNDEF,
+                is_weak: info >> 4 == STB_WEAK,
+            });
+        }
+
+        Ok(symbols)
+    }
+
+    /// Returns the `DT_NEEDED` entries of the dynamic section.
+    pub fn needed_libraries(&self) -> io::Result<Vec<&'a CStr>> {
+        let Some(dynamic) = self.section_of_kind(SHT_DYNAMIC) else {
+            return Ok(vec![]);
+        };
+
+        let strtab = self.linked_section_data(dynamic)?;
+        let entsize = if self.is_64 { 16 } else { 8 };
+
+        let mut needed = vec![];
+
+        for i in 0..dynamic.size / entsize {
+            let offset = dynamic.offset + i * entsize;
+
+            let (tag, val) = if self.is_64 {
+                (self.u64_at(offset)?, self.u64_at(offset + 8)?)
+            } else {
+                (self.u32_at(offset)? as u64, self.u32_at(offset + 4)? as u64)
+            };
+
+            match tag {
+                DT_NULL => break,
+                DT_NEEDED => needed.push(str_at(strtab, val as u32)?),
+                _ => {}
+            }
+        }
+
+        Ok(needed)
+    }
+
+    fn section_of_kind(&self, kind: u32) -> Option<&SectionHeader> {
+        self.sections.iter().find(|section| section.kind == kind)
+    }
+
+    fn linked_section_data(&self, section: &SectionHeader) -> io::Result<&'a [u8]> {
+        let linked = self
+            .sections
+            .get(section.link as usize)
+            .ok_or_else(|| invalid_data("invalid ELF section link"))?;
+
+        let range = self.range(linked.offset, linked.size)?;
+        Ok(&self.bytes[range])
+    }
+
+    fn section_header(&self, offset: u64) -> io::Result<SectionHeader> {
+        if self.is_64 {
+            Ok(SectionHeader {
+                kind: self.u32_at(offset + 0x04)?,
+                offset: self.u64_at(offset + 0x18)?,
+                size: self.u64_at(offset + 0x20)?,
+                link: self.u32_at(offset + 0x28)?,
+            })
+        } else {
+            Ok(SectionHeader {
+                kind: self.u32_at(offset + 0x04)?,
+                offset: self.u32_at(offset + 0x10)? as u64,
+                size: self.u32_at(offset + 0x14)? as u64,
+                link: self.u32_at(offset + 0x18)?,
+            })
+        }
+    }
+
+    fn range(&self, offset: u64, len: u64) -> io::Result<Range<usize>> {
+        offset
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len() as u64)
+            .map(|end| offset as usize..end as usize)
+            .ok_or_else(|| invalid_data("ELF image is truncated"))
+    }
+
+    fn array_at<const N: usize>(&self, offset: u64) -> io::Result<[u8; N]> {
+        let range = self.range(offset, N as u64)?;
+        Ok(self.bytes[range].try_into().unwrap())
+    }
+
+    fn u8_at(&self, offset: u64) -> io::Result<u8> {
+        self.array_at::<1>(offset).map(|[byte]| byte)
+    }
+
+    fn u16_at(&self, offset: u64) -> io::Result<u16> {
+        let bytes = self.array_at(offset)?;
+
+        Ok(if self.is_le {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32_at(&self, offset: u64) -> io::Result<u32> {
+        let bytes = self.array_at(offset)?;
+
+        Ok(if self.is_le {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn u64_at(&self, offset: u64) -> io::Result<u64> {
+        let bytes = self.array_at(offset)?;
+
+        Ok(if self.is_le {
+            u64::from_le_bytes(bytes)
+        } else {
+            u64::from_be_bytes(bytes)
+        })
+    }
+}
+
+fn str_at(strtab: &[u8], offset: u32) -> io::Result<&CStr> {
+    strtab
+        .get(offset as usize..)
+        .and_then(|bytes| CStr::from_bytes_until_nul(bytes).ok())
+        .ok_or_else(|| invalid_data("invalid ELF string table offset"))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf, process::Command, sync::OnceLock};
+
+    use libloading::library_filename;
+
+    use super::*;
+    use crate::os::inspect_library;
+
+    /// Builds the test library, and reads it from the target directory of this test.
+    fn test_library() -> &'static [u8] {
+        static BYTES: OnceLock<Vec<u8>> = OnceLock::new();
+
+        BYTES.get_or_init(|| {
+            let manifest_path =
+                concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test-library/Cargo.toml");
+
+            let built = Command::new(env!("CARGO"))
+                .args(["build", "--manifest-path", manifest_path])
+                .status()
+                .unwrap()
+                .success();
+
+            assert!(built, "failed to build test library");
+
+            // The test executable is in the `deps` directory of the profile.
+            let profile_dir: PathBuf = env::current_exe()
+                .unwrap()
+                .ancestors()
+                .nth(2)
+                .unwrap()
+                .into();
+
+            fs::read(profile_dir.join(library_filename("test_library"))).unwrap()
+        })
+    }
+
+    #[test]
+    fn parse_test_library() {
+        let elf = Elf::parse(test_library()).unwrap();
+
+        let symbols = elf.dynamic_symbols().unwrap();
+
+        assert!(symbols.iter().any(|symbol| {
+            symbol.is_defined && symbol.name.to_bytes() == b"__libhotpatch_fn_table"
+        }));
+        assert!(symbols.iter().any(|symbol| !symbol.is_defined));
+
+        let needed = elf.needed_libraries().unwrap();
+        assert!(
+            needed
+                .iter()
+                .any(|needed| needed.to_bytes().starts_with(b"libc.so"))
+        );
+    }
+
+    #[test]
+    fn inspect_test_library() {
+        inspect_library(test_library(), &["__libhotpatch_fn_table"]).unwrap();
+
+        let missing = inspect_library(test_library(), &["__libhotpatch_missing"]).unwrap_err();
+        assert_eq!(missing.to_string(), "missing export __libhotpatch_missing");
+    }
+
+    #[test]
+    fn reject_truncated_library() {
+        let bytes = test_library();
+
+        for len in [0, 4, 64, bytes.len() / 2, bytes.len() - 1] {
+            assert!(Elf::parse(&bytes[..len]).is_err(), "accepted {len} bytes");
+            assert!(inspect_library(&bytes[..len], &[]).is_err());
+        }
+    }
+
+    #[test]
+    fn reject_garbage() {
+        assert!(Elf::parse(&[0xa5; 4096]).is_err());
+
+        // A valid identification followed by garbage.
+        let mut bytes = test_library()[..16].to_vec();
+        bytes.extend((0..4096u32).map(|i| (i.wrapping_mul(2654435761) >> 24) as u8));
+
+        assert!(Elf::parse(&bytes).is_err());
+        assert!(inspect_library(&bytes, &[]).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    ffi::CString,
+    fs::File,
+    io::{self, Read, Write},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    panic::{self, AssertUnwindSafe},
+    time::{Duration, Instant},
+};
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+pub mod crash;
+
+/// Copies an in-memory library image into a sealed `memfd`, without writing it to disk.
+///
+/// `dlopen` can load it from `/proc/self/fd/{fd}`, and the file can be closed once it is
+/// loaded. The descriptor number is at least `min_fd`.
+pub fn create_memfd(name: &str, bytes: &[u8], min_fd: RawFd) -> io::Result<File> {
+    let name = CString::new(name)?;
+
+    // SAFETY: `name` is a valid C string, return value is checked.
+    let fd =
+        unsafe { libc::memfd_create(name.as_ptr(), libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING) };
+
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // SAFETY: `fd` is a newly created file descriptor owned by nobody else.
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    file.write_all(bytes)?;
+
+    let seals = libc::F_SEAL_SEAL | libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_WRITE;
+
+    // SAFETY: `fd` is a valid memfd created with `MFD_ALLOW_SEALING`, return value is checked.
+    if unsafe { libc::fcntl(fd, libc::F_ADD_SEALS, seals) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // SAFETY: `fd` is a valid file descriptor, return value is checked.
+    let dup_fd = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, min_fd) };
+
+    if dup_fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // SAFETY: `dup_fd` is a newly duplicated file descriptor owned by nobody else.
+    Ok(unsafe { File::from_raw_fd(dup_fd) })
+}
+
+/// Runs `f` in a forked child process and returns the bytes it produced.
+///
+/// The outer result fails if the child could not be run or did not finish within
+/// `timeout`, the inner one if `f` returned an error or panicked, or the child was
+/// terminated by a signal. The child exits without running any destructors or exit
+/// handlers of the host process.
+///
+/// Only the calling thread exists in the child, so `f` must not depend on locks held by
+/// other threads of the process.
+pub fn run_in_child<F>(timeout: Duration, f: F) -> io::Result<io::Result<Vec<u8>>>
+where
+    F: FnOnce() -> io::Result<Vec<u8>>,
+{
+    let mut fds = [0; 2];
+
+    // SAFETY: `fds` is a valid array of two file descriptors, return value is checked.
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // SAFETY: both file descriptors were just created by `pipe2`.
+    let (read_fd, write_fd) =
+        unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+    // SAFETY: the child only runs `f` and exits with `_exit`.
+    let pid = unsafe { libc::fork() };
+
+    if pid < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    if pid == 0 {
+        drop(read_fd);
+        let mut pipe = File::from(write_fd);
+
+        let code = match panic::catch_unwind(AssertUnwindSafe(f)) {
+            Ok(Ok(bytes)) => i32::from(pipe.write_all(&bytes).is_err()),
+            Ok(Err(e)) => {
+                let _ = pipe.write_all(e.to_string().as_bytes());
+                1
+            }
+            Err(_) => 2,
+        };
+
+        // SAFETY: terminates the child without unwinding into the host's code.
+        unsafe { libc::_exit(code) };
+    }
+
+    drop(write_fd);
+
+    let output = read_until_eof(File::from(read_fd), timeout);
+
+    if output.is_err() {
+        // SAFETY: `pid` is our child process, which has not been waited on yet.
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+        }
+    }
+
+    let mut status = 0;
+
+    // SAFETY: `pid` is our child process, return value is checked.
+    if unsafe { libc::waitpid(pid, &mut status, 0) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let output = output?;
+
+    if libc::WIFSIGNALED(status) {
+        return Ok(Err(io::Error::other(format!(
+            "child process was terminated by signal {}",
+            libc::WTERMSIG(status)
+        ))));
+    }
+
+    Ok(match libc::WEXITSTATUS(status) {
+        0 => Ok(output),
+        1 => Err(io::Error::other(String::from_utf8_lossy(&output))),
+        _ => Err(io::Error::other("child process panicked")),
+    })
+}
+
+fn read_until_eof(mut file: File, timeout: Duration) -> io::Result<Vec<u8>> {
+    let deadline = Instant::now() + timeout;
+
+    let mut output = vec![];
+    let mut buf = [0; 4096];
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        let mut pollfd = libc::pollfd {
+            fd: file.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+
+        // SAFETY: `pollfd` is a valid `pollfd` structure, return value is checked.
+        let res = unsafe { libc::poll(&mut pollfd, 1, remaining.as_millis() as i32) };
+
+        if res < 0 {
+            let e = io::Error::last_os_error();
+
+            if e.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+
+            return Err(e);
+        } else if res == 0 {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+
+        match file.read(&mut buf)? {
+            0 => return Ok(output),
+            n => output.extend_from_slice(&buf[..n]),
+        }
+    }
+}
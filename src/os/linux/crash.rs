@@ -0,0 +1,344 @@
+use std::{
+    arch::global_asm,
+    cell::Cell,
+    ffi::{c_int, c_void},
+    mem,
+    ops::Range,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    sync::{Once, OnceLock},
+    thread::Result as ThreadResult,
+};
+
+use crate::os::Crash;
+
+/// Saved state of a [`guard`] call, restored when a fault is caught.
+#[repr(C)]
+struct Guard {
+    jmp_buf: [u64; 24],
+    text: Range<usize>,
+    crash: Option<Crash>,
+}
+
+thread_local! {
+    static CURRENT_GUARD: Cell<*mut Guard> = const { Cell::new(ptr::null_mut()) };
+}
+
+/// The actions for `SIGSEGV` and `SIGBUS` before [`handle_fault`] was installed.
+static PREV_ACTIONS: OnceLock<[libc::sigaction; 2]> = OnceLock::new();
+
+unsafe extern "C" {
+    /// Saves the callee-saved registers and stack pointer in `jmp_buf` and calls `f(data)`.
+    ///
+    /// Returns 0 when `f` returns, or 1 when [`__libhotpatch_guard_jump`] is called with
+    /// the same `jmp_buf`.
+    fn __libhotpatch_guard_call(
+        jmp_buf: *mut u64,
+        f: unsafe extern "C" fn(*mut c_void),
+        data: *mut c_void,
+    ) -> u32;
+
+    /// Restores the state saved in `jmp_buf`, returning 1 from [`__libhotpatch_guard_call`].
+    fn __libhotpatch_guard_jump(jmp_buf: *mut u64) -> !;
+}
+
+#[cfg(target_arch = "x86_64")]
+global_asm!(
+    ".pushsection .text.__libhotpatch_guard_call,\"ax\",@progbits",
+    ".p2align 4",
+    ".globl __libhotpatch_guard_call",
+    ".hidden __libhotpatch_guard_call",
+    ".type __libhotpatch_guard_call,@function",
+    "__libhotpatch_guard_call:",
+    "mov [rdi], rbx",
+    "mov [rdi + 8], rbp",
+    "mov [rdi + 16], r12",
+    "mov [rdi + 24], r13",
+    "mov [rdi + 32], r14",
+    "mov [rdi + 40], r15",
+    "lea rax, [rsp + 8]",
+    "mov [rdi + 48], rax",
+    "mov rax, [rsp]",
+    "mov [rdi + 56], rax",
+    "sub rsp, 8",
+    "mov rdi, rdx",
+    "call rsi",
+    "add rsp, 8",
+    "xor eax, eax",
+    "ret",
+    ".size __libhotpatch_guard_call, . - __libhotpatch_guard_call",
+    ".globl __libhotpatch_guard_jump",
+    ".hidden __libhotpatch_guard_jump",
+    ".type __libhotpatch_guard_jump,@function",
+    "__libhotpatch_guard_jump:",
+    "mov rbx, [rdi]",
+    "mov rbp, [rdi + 8]",
+    "mov r12, [rdi + 16]",
+    "mov r13, [rdi + 24]",
+    "mov r14, [rdi + 32]",
+    "mov r15, [rdi + 40]",
+    "mov rsp, [rdi + 48]",
+    "mov eax, 1",
+    "jmp qword ptr [rdi + 56]",
+    ".size __libhotpatch_guard_jump, . - __libhotpatch_guard_jump",
+    ".popsection",
+);
+
+#[cfg(target_arch = "aarch64")]
+global_asm!(
+    ".pushsection .text.__libhotpatch_guard_call,\"ax\",@progbits",
+    ".p2align 2",
+    ".globl __libhotpatch_guard_call",
+    ".hidden __libhotpatch_guard_call",
+    ".type __libhotpatch_guard_call,@function",
+    "__libhotpatch_guard_call:",
+    "stp x19, x20, [x0]",
+    "stp x21, x22, [x0, #16]",
+    "stp x23, x24, [x0, #32]",
+    "stp x25, x26, [x0, #48]",
+    "stp x27, x28, [x0, #64]",
+    "stp x29, x30, [x0, #80]",
+    "mov x9, sp",
+    "str x9, [x0, #96]",
+    "stp d8, d9, [x0, #104]",
+    "stp d10, d11, [x0, #120]",
+    "stp d12, d13, [x0, #136]",
+    "stp d14, d15, [x0, #152]",
+    "stp x29, x30, [sp, #-16]!",
+    "mov x29, sp",
+    "mov x0, x2",
+    "blr x1",
+    "ldp x29, x30, [sp], #16",
+    "mov w0, #0",
+    "ret",
+    ".size __libhotpatch_guard_call, . - __libhotpatch_guard_call",
+    ".globl __libhotpatch_guard_jump",
+    ".hidden __libhotpatch_guard_jump",
+    ".type __libhotpatch_guard_jump,@function",
+    "__libhotpatch_guard_jump:",
+    "ldp x19, x20, [x0]",
+    "ldp x21, x22, [x0, #16]",
+    "ldp x23, x24, [x0, #32]",
+    "ldp x25, x26, [x0, #48]",
+    "ldp x27, x28, [x0, #64]",
+    "ldp x29, x30, [x0, #80]",
+    "ldr x9, [x0, #96]",
+    "mov sp, x9",
+    "ldp d8, d9, [x0, #104]",
+    "ldp d10, d11, [x0, #120]",
+    "ldp d12, d13, [x0, #136]",
+    "ldp d14, d15, [x0, #152]",
+    "mov w0, #1",
+    "ret",
+    ".size __libhotpatch_guard_jump, . - __libhotpatch_guard_jump",
+    ".popsection",
+);
+
+/// Calls `f`, catching faults raised while the program counter is in `text`.
+///
+/// A caught fault abandons the call: the stack frames between the fault and this function
+/// are discarded without running any destructors.
+pub fn guard<F: FnOnce() -> R, R>(text: Range<usize>, f: F) -> Result<R, Crash> {
+    install_handlers();
+
+    let mut guard = Guard {
+        jmp_buf: [0; 24],
+        text,
+        crash: None,
+    };
+
+    let mut state = (Some(f), None::<ThreadResult<R>>);
+
+    unsafe extern "C" fn call<F: FnOnce() -> R, R>(data: *mut c_void) {
+        // SAFETY: `data` points to the `state` tuple of the enclosing `guard` call.
+        let (f, output) = unsafe { &mut *data.cast::<(Option<F>, Option<ThreadResult<R>>)>() };
+
+        if let Some(f) = f.take() {
+            // Unwinding through `__libhotpatch_guard_call` is not possible.
+            *output = Some(panic::catch_unwind(AssertUnwindSafe(f)));
+        }
+    }
+
+    let prev_guard = CURRENT_GUARD.replace(&raw mut guard);
+
+    // SAFETY: `jmp_buf` is large enough for all supported architectures, `call` matches the
+    // type of `state`.
+    let res = unsafe {
+        __libhotpatch_guard_call(
+            guard.jmp_buf.as_mut_ptr(),
+            call::<F, R>,
+            (&raw mut state).cast(),
+        )
+    };
+
+    CURRENT_GUARD.set(prev_guard);
+
+    match (res, state.1) {
+        (0, Some(Ok(output))) => Ok(output),
+        (0, Some(Err(payload))) => panic::resume_unwind(payload),
+        _ => Err(guard.crash.expect("guarded call returned without a crash")),
+    }
+}
+
+/// Returns the address range of the executable segments of the object containing `addr`.
+pub fn executable_range(addr: *const c_void) -> Option<Range<usize>> {
+    struct Search {
+        addr: usize,
+        range: Option<Range<usize>>,
+    }
+
+    unsafe extern "C" fn callback(
+        info: *mut libc::dl_phdr_info,
+        _size: libc::size_t,
+        data: *mut c_void,
+    ) -> c_int {
+        // SAFETY: pointers are provided by `dl_iterate_phdr` and the enclosing function.
+        let (info, search) = unsafe { (&*info, &mut *data.cast::<Search>()) };
+
+        // SAFETY: `dlpi_phdr` points to `dlpi_phnum` program headers.
+        let phdrs = unsafe { std::slice::from_raw_parts(info.dlpi_phdr, info.dlpi_phnum as usize) };
+
+        let base = info.dlpi_addr as usize;
+
+        let segments = phdrs
+            .iter()
+            .filter(|phdr| phdr.p_type == libc::PT_LOAD)
+            .map(|phdr| {
+                let start = base + phdr.p_vaddr as usize;
+                (
+                    start..start + phdr.p_memsz as usize,
+                    phdr.p_flags & libc::PF_X != 0,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        if !segments
+            .iter()
+            .any(|(range, _)| range.contains(&search.addr))
+        {
+            return 0;
+        }
+
+        search.range = segments
+            .into_iter()
+            .filter(|&(_, is_executable)| is_executable)
+            .map(|(range, _)| range)
+            .reduce(|a, b| a.start.min(b.start)..a.end.max(b.end));
+
+        1
+    }
+
+    let mut search = Search {
+        addr: addr as usize,
+        range: None,
+    };
+
+    // SAFETY: `callback` matches the type of `search`.
+    unsafe {
+        libc::dl_iterate_phdr(Some(callback), (&raw mut search).cast());
+    }
+
+    search.range
+}
+
+fn install_handlers() {
+    static INSTALL: Once = Once::new();
+
+    INSTALL.call_once(|| {
+        // SAFETY: POD C types that are safe to zero initialize.
+        let (mut prev_actions, mut action) =
+            unsafe { mem::zeroed::<([libc::sigaction; 2], libc::sigaction)>() };
+
+        action.sa_sigaction = handle_fault as *const () as libc::sighandler_t;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+
+        // SAFETY: all actions are initialized. The previous actions are saved before the
+        // handler, which reads them, is installed.
+        unsafe {
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(libc::SIGSEGV, ptr::null(), &mut prev_actions[0]);
+            libc::sigaction(libc::SIGBUS, ptr::null(), &mut prev_actions[1]);
+
+            let _ = PREV_ACTIONS.set(prev_actions);
+
+            libc::sigaction(libc::SIGSEGV, &action, ptr::null_mut());
+            libc::sigaction(libc::SIGBUS, &action, ptr::null_mut());
+        }
+    });
+}
+
+unsafe extern "C" fn handle_fault(signal: c_int, info: *mut libc::siginfo_t, context: *mut c_void) {
+    let context = context.cast::<libc::ucontext_t>();
+
+    // SAFETY: `context` is provided by the kernel for `SA_SIGINFO` handlers.
+    #[cfg(target_arch = "x86_64")]
+    let pc = unsafe { (*context).uc_mcontext.gregs[libc::REG_RIP as usize] as usize };
+    #[cfg(target_arch = "aarch64")]
+    let pc = unsafe { (*context).uc_mcontext.pc as usize };
+
+    let guard = CURRENT_GUARD.get();
+
+    // SAFETY: a non-null guard is alive for the duration of its `guard` call on this thread.
+    if let Some(guard) = unsafe { guard.as_mut() }
+        && guard.text.contains(&pc)
+    {
+        guard.crash = Some(Crash {
+            signal: match signal {
+                libc::SIGBUS => "SIGBUS",
+                _ => "SIGSEGV",
+            },
+            // SAFETY: `info` is provided by the kernel for `SA_SIGINFO` handlers.
+            fault_addr: unsafe { (*info).si_addr() as usize },
+            pc,
+        });
+
+        let jmp_buf = guard.jmp_buf.as_mut_ptr();
+        let jump = __libhotpatch_guard_jump as *const () as usize;
+
+        // Resume in `__libhotpatch_guard_jump` after returning from the handler, which
+        // restores the signal mask of the guarded code.
+        // SAFETY: `context` is provided by the kernel for `SA_SIGINFO` handlers.
+        unsafe {
+            #[cfg(target_arch = "x86_64")]
+            {
+                (*context).uc_mcontext.gregs[libc::REG_RIP as usize] = jump as _;
+                (*context).uc_mcontext.gregs[libc::REG_RDI as usize] = jmp_buf as _;
+            }
+            #[cfg(target_arch = "aarch64")]
+            {
+                (*context).uc_mcontext.pc = jump as _;
+                (*context).uc_mcontext.regs[0] = jmp_buf as _;
+            }
+        }
+
+        return;
+    }
+
+    let Some(prev_actions) = PREV_ACTIONS.get() else {
+        return;
+    };
+
+    let prev = match signal {
+        libc::SIGBUS => &prev_actions[1],
+        _ => &prev_actions[0],
+    };
+
+    // SAFETY: forwarding to the previously installed handler, or restoring the previous
+    // action so that the fault is raised again when the handler returns.
+    unsafe {
+        if prev.sa_sigaction == libc::SIG_DFL || prev.sa_sigaction == libc::SIG_IGN {
+            libc::sigaction(signal, prev, ptr::null_mut());
+        } else if prev.sa_flags & libc::SA_SIGINFO != 0 {
+            let handler = mem::transmute::<
+                libc::sighandler_t,
+                unsafe extern "C" fn(c_int, *mut libc::siginfo_t, *mut c_void),
+            >(prev.sa_sigaction);
+            handler(signal, info, context.cast());
+        } else {
+            let handler = mem::transmute::<libc::sighandler_t, unsafe extern "C" fn(c_int)>(
+                prev.sa_sigaction,
+            );
+            handler(signal);
+        }
+    }
+}
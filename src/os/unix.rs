@@ -1,11 +1,13 @@
 use std::{
     ffi::{CStr, OsStr, c_void},
-    mem,
+    io, mem,
     os::unix::ffi::OsStrExt,
     path::PathBuf,
 };
 
-use libc::{Dl_info, dladdr};
+use libc::{Dl_info, c_int, dladdr};
+
+use crate::config::LoadFlags;
 
 #[inline(never)]
 pub fn current_module_path() -> Option<PathBuf> {
@@ -23,6 +25,35 @@ pub fn current_module_path() -> Option<PathBuf> {
     })
 }
 
+pub fn dlopen_flags(flags: LoadFlags) -> c_int {
+    // Builds are never unloaded.
+    let mut dlopen_flags = libc::RTLD_NODELETE;
+
+    dlopen_flags |= if flags.bind_now {
+        libc::RTLD_NOW
+    } else {
+        libc::RTLD_LAZY
+    };
+
+    dlopen_flags |= if flags.global {
+        libc::RTLD_GLOBAL
+    } else {
+        libc::RTLD_LOCAL
+    };
+
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    if flags.deep_bind {
+        dlopen_flags |= libc::RTLD_DEEPBIND;
+    }
+
+    #[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+    if flags.deep_bind {
+        log::warn!("RTLD_DEEPBIND is not supported, ignoring");
+    }
+
+    dlopen_flags
+}
+
 #[inline]
 pub fn aligned_alloc(size: usize, align: usize) -> *mut c_void {
     unsafe { libc::aligned_alloc(align, size) }
@@ -34,3 +65,55 @@ pub unsafe fn free(ptr: *mut c_void) {
         libc::free(ptr);
     }
 }
+
+pub fn process_exists(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+
+    // SAFETY: signal 0 only performs permission and existence checks.
+    let res = unsafe { libc::kill(pid, 0) };
+
+    res == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Returns whether `name` resolves to a symbol in the global scope of the process.
+pub fn is_symbol_loaded(name: &CStr) -> bool {
+    // SAFETY: `name` is a valid C string.
+    unsafe { !libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr()).is_null() }
+}
+
+/// Returns whether `name` resolves to a symbol of the loaded library `library` or of its
+/// dependencies, which need not be in the global scope.
+pub fn is_symbol_in_library(library: &CStr, name: &CStr) -> bool {
+    // SAFETY: `library` is a valid C string, `RTLD_NOLOAD` never loads a new library.
+    let handle = unsafe { libc::dlopen(library.as_ptr(), libc::RTLD_LAZY | libc::RTLD_NOLOAD) };
+
+    if handle.is_null() {
+        return false;
+    }
+
+    // SAFETY: `handle` is a valid handle returned by `dlopen`, and `name` a valid C string.
+    unsafe {
+        let is_found = !libc::dlsym(handle, name.as_ptr()).is_null();
+        libc::dlclose(handle);
+        is_found
+    }
+}
+
+/// Returns whether a shared library named `name` is loaded in the process.
+pub fn is_library_loaded(name: &CStr) -> bool {
+    // SAFETY: `name` is a valid C string, `RTLD_NOLOAD` never loads a new library.
+    let handle = unsafe { libc::dlopen(name.as_ptr(), libc::RTLD_LAZY | libc::RTLD_NOLOAD) };
+
+    if handle.is_null() {
+        return false;
+    }
+
+    // SAFETY: `handle` is a valid handle returned by `dlopen`.
+    unsafe {
+        libc::dlclose(handle);
+    }
+
+    true
+}
@@ -6,7 +6,9 @@ use std::{
 
 use windows_sys::{
     Win32::{
-        Foundation::{HANDLE, MAX_PATH},
+        Foundation::{
+            CloseHandle, ERROR_INVALID_PARAMETER, GetLastError, HANDLE, MAX_PATH, STILL_ACTIVE,
+        },
         System::{
             LibraryLoader::{
                 GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS,
@@ -14,6 +16,7 @@ use windows_sys::{
                 GetModuleHandleExW,
             },
             Memory::{GetProcessHeap, HeapAlloc, HeapFree},
+            Threading::{GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION},
         },
     },
     core::PCWSTR,
@@ -84,3 +87,25 @@ pub unsafe fn free(ptr: *mut c_void) {
         HeapFree(process_heap, 0, base_ptr);
     }
 }
+
+pub fn process_exists(pid: u32) -> bool {
+    // SAFETY: return value is checked.
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+
+    // Processes of other users can not be opened, only a missing process is reported as an
+    // invalid parameter.
+    if process.is_null() {
+        // SAFETY: reads the error of the failed call on this thread.
+        return unsafe { GetLastError() } != ERROR_INVALID_PARAMETER;
+    }
+
+    let mut exit_code = 0;
+
+    // SAFETY: `process` is a valid process handle, closed exactly once.
+    unsafe {
+        let res = GetExitCodeProcess(process, &mut exit_code);
+        CloseHandle(process);
+
+        res == 0 || exit_code == STILL_ACTIVE as u32
+    }
+}
@@ -0,0 +1,464 @@
+//! Statics that keep their value when the library is hot-patched, see [`persistent!`].
+
+#[cfg(feature = "checked")]
+use std::panic::{self, AssertUnwindSafe};
+use std::{
+    any,
+    hash::Hash,
+    io,
+    mem::{self, MaybeUninit},
+    ops::Deref,
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering as AtomicOrdering},
+};
+
+#[cfg(feature = "checked")]
+use serde::{Serialize, de::DeserializeOwned};
+use xxhash_rust::xxh3::Xxh3;
+
+#[cfg(feature = "checked")]
+use crate::codec::{HotpatchCodec, MessagePack};
+use crate::{
+    abi::{
+        boxed::{Box as AbiBox, BoxedSlice},
+        str::BoxedStr,
+    },
+    watcher::Watcher,
+};
+
+/// Declares statics whose values are shared by every generation of the library.
+///
+/// ```
+/// use std::sync::atomic::AtomicU32;
+///
+/// libhotpatch::persistent! {
+///     #[version(1)]
+///     static FRAMES: AtomicU32 = AtomicU32::new(0);
+/// }
+/// ```
+///
+/// The first generation to access such a static initializes it, and later generations
+/// resolve the same instance through the library that was originally loaded, instead of
+/// a fresh one. Instances are keyed by the path of the static, its version, and a
+/// fingerprint of the name, size and alignment of its type. When the type of a static
+/// changes, the new generation gets a new instance, while generations that still run with
+/// the previous type keep theirs.
+///
+/// The fingerprint can not tell apart layouts that share all three, like those of a struct
+/// whose field changed from `i32` to `f32`, and reusing the instance of one as the other
+/// would be undefined behavior. Every static therefore declares a `#[version(N)]`, which
+/// must be changed whenever the fields of its type are. A static without one does not
+/// compile:
+///
+/// ```compile_fail
+/// use std::sync::atomic::AtomicU32;
+///
+/// libhotpatch::persistent! {
+///     static FRAMES: AtomicU32 = AtomicU32::new(0);
+/// }
+/// ```
+///
+/// A static marked `#[migrate(serde)]` (with the "checked" feature) is migrated instead:
+/// when its type changes, its value is serialized by the generation that created it and
+/// deserialized into the new type, both with named MessagePack. Fields can then be added
+/// (with `#[serde(default)]`), removed and reordered, and other conversions can be written
+/// with `#[serde(from = "...")]`. The static is reset when its value fails to migrate.
+/// Generations that still run with the previous type keep updating the previous value.
+/// The fingerprint of such a static also covers the serialized form of its type, traced
+/// with `serde-reflection`, so that it needs no version.
+///
+/// ```
+/// # #[cfg(feature = "checked")] {
+/// use std::sync::Mutex;
+///
+/// #[derive(Default, serde::Serialize, serde::Deserialize)]
+/// struct Player {
+///     hp: u32,
+///     #[serde(default)]
+///     mana: u32,
+/// }
+///
+/// libhotpatch::persistent! {
+///     #[migrate(serde)]
+///     static PLAYER: Mutex<Player> = Mutex::new(Player::default());
+/// }
+/// # }
+/// ```
+///
+/// Values are never dropped. They may refer to data of the generation that created them,
+/// like `&'static str` literals, function pointers or trait objects, since builds are never
+/// unloaded.
+#[macro_export]
+macro_rules! persistent {
+    (@static [$($attr:tt)*] [$($with:tt)*] #[migrate(serde)] $($rest:tt)*) => {
+        $crate::persistent!(@static [$($attr)*] [$($with)* .with_serde()] $($rest)*);
+    };
+    (@static [$($attr:tt)*] [$($with:tt)*] #[version($version:expr)] $($rest:tt)*) => {
+        $crate::persistent!(@static [$($attr)*] [$($with)* .with_version($version)] $($rest)*);
+    };
+    (@static [$($attr:tt)*] [$($with:tt)*] #[$meta:meta] $($rest:tt)*) => {
+        $crate::persistent!(@static [$($attr)* #[$meta]] [$($with)*] $($rest)*);
+    };
+    (
+        @static [$($attr:tt)*] []
+        $vis:vis static $name:ident: $ty:ty = $init:expr; $($rest:tt)*
+    ) => {
+        ::std::compile_error!(::std::concat!(
+            "persistent static `",
+            ::std::stringify!($name),
+            "` needs a `#[version(N)]`, changed whenever the fields of its type are, ",
+            "or `#[migrate(serde)]`",
+        ));
+
+        $crate::persistent!(
+            @static [$($attr)*] [.with_version(0)]
+            $vis static $name: $ty = $init; $($rest)*
+        );
+    };
+    (
+        @static [$($attr:tt)*] [$($with:tt)*]
+        $vis:vis static $name:ident: $ty:ty = $init:expr; $($rest:tt)*
+    ) => {
+        $($attr)*
+        $vis static $name: $crate::Persistent<$ty> = $crate::Persistent::new(
+            ::std::concat!(::std::module_path!(), "::", ::std::stringify!($name)),
+            || $init,
+        )$($with)*;
+
+        $crate::persistent!($($rest)*);
+    };
+    () => {};
+    ($($rest:tt)+) => {
+        $crate::persistent!(@static [] [] $($rest)+);
+    };
+}
+
+/// A static declared with [`persistent!`].
+pub struct Persistent<T> {
+    name: &'static str,
+    init: fn() -> T,
+    serialize: Option<SerializeFn>,
+    deserialize: Option<DeserializeFn<T>>,
+    /// Changed along with the fields of the type, see [`persistent!`].
+    version: u32,
+    /// Hashes the serialized form of the type.
+    schema: Option<fn() -> Option<u128>>,
+    /// The instance resolved by this generation.
+    value: AtomicPtr<T>,
+}
+
+/// Serializes a persistent value, in the generation that created it, for the generation
+/// that migrates it.
+///
+/// Returns `false` if the value could not be serialized.
+type SerializeFn = extern "C" fn(*const (), *mut BoxedSlice<u8>) -> bool;
+
+/// Deserializes a value migrated from another generation.
+type DeserializeFn<T> = fn(&[u8]) -> io::Result<T>;
+
+/// Drops a value, in the generation that created it.
+pub type DropFn = extern "C" fn(*mut ());
+
+/// The instances of persistent statics, owned by the library that was originally loaded.
+///
+/// Instances are prepended to a list that is only shrunk by [`Registry::clear`], and whose
+/// entries are allocated with the allocator shared by all generations.
+#[repr(C)]
+pub struct Registry {
+    head: AtomicPtr<Entry>,
+}
+
+#[repr(C)]
+pub(crate) struct Entry {
+    name: BoxedStr,
+    fingerprint: u128,
+    value: *mut (),
+    serialize: Option<SerializeFn>,
+    drop: Option<DropFn>,
+    next: *mut Entry,
+}
+
+impl<T> Persistent<T> {
+    #[doc(hidden)]
+    pub const fn new(name: &'static str, init: fn() -> T) -> Self {
+        Self {
+            name,
+            init,
+            serialize: None,
+            deserialize: None,
+            version: 0,
+            schema: None,
+            value: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Tells apart the layouts of the type that share a fingerprint.
+    #[doc(hidden)]
+    pub const fn with_version(self, version: u32) -> Self {
+        Self { version, ..self }
+    }
+
+    /// Migrates values of the previous type of the static with `serde`.
+    #[cfg(feature = "checked")]
+    #[doc(hidden)]
+    pub const fn with_serde(self) -> Self
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        Self {
+            serialize: Some(serialize::<T>),
+            deserialize: Some(MessagePack::deserialize::<T>),
+            schema: Some(crate::schema::hash_schema::<T>),
+            ..self
+        }
+    }
+
+    /// Hashes the version and the serialized form of the type, which its fingerprint does
+    /// not cover.
+    fn layout(&self) -> u128 {
+        let mut hasher = Xxh3::new();
+        self.version.hash(&mut hasher);
+
+        if let Some(schema) = self.schema {
+            let schema = schema();
+
+            if schema.is_none() {
+                let name = self.name;
+                log::warn!(
+                    "the type of persistent static {name} could not be traced, changes of its \
+                     fields are only detected by its version"
+                );
+            }
+
+            schema.hash(&mut hasher);
+        }
+
+        hasher.digest128()
+    }
+
+    /// Creates the value of the static, from the value it had with its `previous` type if
+    /// possible.
+    fn create(&self, previous: Option<&Entry>) -> T {
+        let Some(previous) = previous else {
+            return (self.init)();
+        };
+
+        let name = self.name;
+
+        let Some(deserialize) = self.deserialize else {
+            log::warn!("the type of persistent static {name} changed, it is reset");
+            return (self.init)();
+        };
+
+        let migrated = previous
+            .serialize()
+            .ok_or_else(|| io::Error::other("the previous value could not be serialized"))
+            .and_then(|bytes| deserialize(&bytes));
+
+        match migrated {
+            Ok(value) => {
+                log::info!("migrated persistent static {name} to its new type");
+                value
+            }
+            Err(e) => {
+                log::warn!("error migrating persistent static {name}, it is reset: {e}");
+                (self.init)()
+            }
+        }
+    }
+}
+
+impl<T: Send + Sync> Persistent<T> {
+    pub fn get(&self) -> &T {
+        let mut value = self.value.load(AtomicOrdering::Acquire);
+
+        if value.is_null() {
+            value = Registry::get().get_or_insert(
+                self.name,
+                self.layout(),
+                self.serialize,
+                None,
+                |previous| self.create(previous),
+            );
+            self.value.store(value, AtomicOrdering::Release);
+        }
+
+        // SAFETY: instances are never freed, and are only shared between statics with the
+        // same name and type fingerprint.
+        unsafe { &*value }
+    }
+}
+
+impl<T: Send + Sync> Deref for Persistent<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.get()
+    }
+}
+
+impl Registry {
+    pub const fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// The registry of the original library, or of this generation if it can not be
+    /// hot-patched.
+    fn get() -> &'static Registry {
+        static LOCAL: Registry = Registry::new();
+
+        Watcher::get().map_or(&LOCAL, Watcher::persistent)
+    }
+
+    /// Resolves the value of the static `name` of type `T` and `layout`, or inserts the value
+    /// created from the newest value of the static with another type, if any.
+    pub(crate) fn get_or_insert<T>(
+        &self,
+        name: &str,
+        layout: u128,
+        serialize: Option<SerializeFn>,
+        drop_fn: Option<DropFn>,
+        create: impl FnOnce(Option<&Entry>) -> T,
+    ) -> *mut T {
+        let fingerprint = fingerprint::<T>(name, layout);
+
+        let mut head = self.head.load(AtomicOrdering::Acquire);
+
+        if let Some(value) = Self::find(head, ptr::null_mut(), fingerprint) {
+            return value.cast();
+        }
+
+        let previous = Self::iter(head, ptr::null_mut()).find(|entry| *entry.name == *name);
+
+        let value = AbiBox::new(create(previous)).into_raw();
+
+        let entry = AbiBox::new(Entry {
+            name: BoxedStr::new(name),
+            fingerprint,
+            value: value.cast(),
+            serialize,
+            drop: drop_fn,
+            next: head,
+        })
+        .into_raw();
+
+        loop {
+            match self.head.compare_exchange(
+                head,
+                entry,
+                AtomicOrdering::AcqRel,
+                AtomicOrdering::Acquire,
+            ) {
+                Ok(_) => return value,
+                Err(new_head) => {
+                    // Another thread may have inserted the same static in the meantime.
+                    if let Some(found) = Self::find(new_head, head, fingerprint) {
+                        // SAFETY: the entry and its value were never shared.
+                        unsafe {
+                            drop(AbiBox::from_raw(entry));
+                            drop(AbiBox::from_raw(value));
+                        }
+                        return found.cast();
+                    }
+
+                    // SAFETY: the entry was never shared.
+                    unsafe { (*entry).next = new_head };
+                    head = new_head;
+                }
+            }
+        }
+    }
+
+    /// Removes every entry, dropping the values that have a [`DropFn`].
+    ///
+    /// # Safety
+    ///
+    /// No reference to the values may be used afterwards.
+    pub(crate) unsafe fn clear(&self) {
+        let mut entry = self.head.swap(ptr::null_mut(), AtomicOrdering::AcqRel);
+
+        while !entry.is_null() {
+            // SAFETY: the entry was allocated by `get_or_insert`, and is no longer shared.
+            let current = unsafe { AbiBox::from_raw(entry) };
+            entry = current.next;
+
+            if let Some(drop_fn) = current.drop {
+                drop_fn(current.value);
+            }
+        }
+    }
+
+    /// Finds the value of the static with `fingerprint` among the entries from `head` to
+    /// `end`.
+    fn find(head: *mut Entry, end: *mut Entry, fingerprint: u128) -> Option<*mut ()> {
+        Self::iter(head, end)
+            .find(|entry| entry.fingerprint == fingerprint)
+            .map(|entry| entry.value)
+    }
+
+    fn iter(head: *mut Entry, end: *mut Entry) -> impl Iterator<Item = &'static Entry> {
+        let mut entry = head;
+
+        std::iter::from_fn(move || {
+            if entry == end {
+                return None;
+            }
+
+            // SAFETY: entries are never freed once they are shared.
+            let current = unsafe { entry.as_ref()? };
+            entry = current.next;
+            Some(current)
+        })
+    }
+}
+
+impl Entry {
+    /// Serializes the value, in the generation that created it.
+    fn serialize(&self) -> Option<BoxedSlice<u8>> {
+        let serialize = self.serialize?;
+        let mut output = MaybeUninit::uninit();
+
+        // SAFETY: `serialize` writes the output if it returns `true`.
+        serialize(self.value, output.as_mut_ptr()).then(|| unsafe { output.assume_init() })
+    }
+}
+
+#[cfg(feature = "checked")]
+extern "C" fn serialize<T: Serialize>(value: *const (), output: *mut BoxedSlice<u8>) -> bool {
+    // SAFETY: entries with this function hold a `T` of this generation.
+    let value = unsafe { &*value.cast::<T>() };
+    let mut bytes = vec![];
+
+    // The panic must be caught in this generation.
+    let serialized = panic::catch_unwind(AssertUnwindSafe(|| {
+        MessagePack::serialize(value, &mut bytes)
+    }));
+
+    if !matches!(serialized, Ok(Ok(()))) {
+        return false;
+    }
+
+    // SAFETY: `output` is valid for writes.
+    unsafe { output.write(BoxedSlice::new(&bytes)) };
+    true
+}
+
+/// Drops a value that was allocated by [`Registry::get_or_insert`].
+pub extern "C" fn drop_value<T>(value: *mut ()) {
+    // SAFETY: entries with this function hold a `T` of this generation.
+    drop(unsafe { AbiBox::from_raw(value.cast::<T>()) });
+}
+
+fn fingerprint<T>(name: &str, layout: u128) -> u128 {
+    let mut hasher = Xxh3::new();
+    name.hash(&mut hasher);
+    layout.hash(&mut hasher);
+    any::type_name::<T>().hash(&mut hasher);
+    mem::size_of::<T>().hash(&mut hasher);
+    mem::align_of::<T>().hash(&mut hasher);
+    hasher.digest128()
+}
+
+unsafe impl<T: Send + Sync> Sync for Persistent<T> {}
@@ -0,0 +1,241 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, hash_map::Entry},
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, PoisonError, RwLock,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use crate::{config::Config, persistent::Persistent, scratch};
+
+/// The recordings of this process, shared by every generation so that each recording is
+/// rotated by only one of them.
+static RECORDINGS: Persistent<Mutex<HashMap<u128, Arc<Recording>>>> =
+    Persistent::new(concat!(module_path!(), "::RECORDINGS"), Default::default);
+
+thread_local! {
+    static RECORDER: RefCell<Recorder> = RefCell::default();
+}
+
+/// The recordings a thread has written to, and the buffer of its next call, so that
+/// recording a call takes no process-wide lock.
+#[derive(Default)]
+struct Recorder {
+    recordings: HashMap<u128, Arc<Recording>>,
+    call: Vec<u8>,
+}
+
+/// The recording file of a function.
+///
+/// Calls are appended under the read lock. Once the file reaches half of
+/// `LIBHOTPATCH_RECORD_MAX_BYTES`, it is rotated under the write lock into the previous
+/// recording, which replaces the one before it.
+struct Recording {
+    path: PathBuf,
+    file: RwLock<File>,
+    len: AtomicU64,
+}
+
+/// A recorded call of a `checked` function, as its serialized input and output.
+pub struct RecordedCall {
+    pub input: Vec<u8>,
+    pub output: Vec<u8>,
+}
+
+pub fn recordings_path() -> String {
+    format!("{}/recordings", scratch::scratch_path())
+}
+
+/// The recording of a function, identified by its type hash and name.
+///
+/// Functions whose signature changes get a new recording, since their hash changes.
+fn recording_path(hash: u128, name: &str) -> PathBuf {
+    PathBuf::from(format!(
+        "{}/{}-{hash:032x}.rec",
+        recordings_path(),
+        file_stem(name)
+    ))
+}
+
+/// The recording that `path` was last rotated into.
+fn previous_path(path: &Path) -> PathBuf {
+    path.with_extension("rec.old")
+}
+
+/// The name of a function as it appears in the names of its recordings, without any `-`.
+fn file_stem(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Appends a call to the recording of a function.
+pub fn record(hash: u128, name: &str, input: &[u8], output: &[u8]) -> io::Result<()> {
+    RECORDER
+        .try_with(|recorder| recorder.borrow_mut().record(hash, name, input, output))
+        .unwrap_or_else(|_| Err(io::Error::other("the thread is exiting")))
+}
+
+impl Recorder {
+    fn record(&mut self, hash: u128, name: &str, input: &[u8], output: &[u8]) -> io::Result<()> {
+        let recording = match self.recordings.entry(hash) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(Recording::get(hash, name)?),
+        };
+
+        self.call.clear();
+        self.call
+            .extend_from_slice(&(input.len() as u64).to_le_bytes());
+        self.call.extend_from_slice(input);
+        self.call
+            .extend_from_slice(&(output.len() as u64).to_le_bytes());
+        self.call.extend_from_slice(output);
+
+        recording.append(&self.call)
+    }
+}
+
+impl Recording {
+    /// The recording of a function, opened by the first thread of any generation that
+    /// records it.
+    fn get(hash: u128, name: &str) -> io::Result<Arc<Self>> {
+        let mut recordings = RECORDINGS.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(recording) = recordings.get(&hash) {
+            return Ok(recording.clone());
+        }
+
+        fs::create_dir_all(recordings_path())?;
+        remove_superseded(hash, name);
+
+        let path = recording_path(hash, name);
+        log::debug!("recording calls of {name} to {path:?}");
+
+        let file = open_append(&path)?;
+
+        let recording = Arc::new(Self {
+            len: AtomicU64::new(file.metadata()?.len()),
+            file: RwLock::new(file),
+            path,
+        });
+
+        recordings.insert(hash, recording.clone());
+
+        Ok(recording)
+    }
+
+    fn append(&self, call: &[u8]) -> io::Result<()> {
+        {
+            let file = self.file.read().unwrap_or_else(PoisonError::into_inner);
+
+            // A single write, so that concurrent recorders do not interleave their calls.
+            (&*file).write_all(call)?;
+        }
+
+        let len = self.len.fetch_add(call.len() as u64, Ordering::Relaxed) + call.len() as u64;
+
+        if len >= Self::max_len() {
+            self.rotate()?;
+        }
+
+        Ok(())
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        let mut file = self.file.write().unwrap_or_else(PoisonError::into_inner);
+
+        // Another thread may have rotated it while this one waited for the lock.
+        if self.len.load(Ordering::Relaxed) < Self::max_len() {
+            return Ok(());
+        }
+
+        log::debug!("rotating recording {:?}", self.path);
+
+        fs::rename(&self.path, previous_path(&self.path))?;
+        *file = open_append(&self.path)?;
+
+        self.len.store(0, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    /// The size at which the file is rotated, so that it and the previous recording stay
+    /// within the configured limit.
+    fn max_len() -> u64 {
+        Config::get().record_max_bytes / 2
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Removes the recordings of other signatures of the function `name`, which can not be
+/// replayed against its current one.
+fn remove_superseded(hash: u128, name: &str) {
+    let Ok(entries) = fs::read_dir(recordings_path()) else {
+        return;
+    };
+
+    let stem = file_stem(name);
+    let hash = format!("{hash:032x}.");
+
+    for entry in entries.filter_map(Result::ok) {
+        let file_name = entry.file_name();
+
+        if let Some((other_stem, rest)) = file_name.to_string_lossy().split_once('-')
+            && other_stem == stem
+            && !rest.starts_with(&hash)
+        {
+            log::debug!("removing superseded recording {:?}", entry.path());
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Reads the recorded calls of a function, if there are any, oldest first.
+pub fn read(hash: u128, name: &str) -> io::Result<Vec<RecordedCall>> {
+    let path = recording_path(hash, name);
+
+    let mut calls = read_file(&previous_path(&path), name)?;
+    calls.extend(read_file(&path, name)?);
+
+    Ok(calls)
+}
+
+fn read_file(path: &Path, name: &str) -> io::Result<Vec<RecordedCall>> {
+    let bytes = match fs::read(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        bytes => bytes?,
+    };
+
+    let mut calls = vec![];
+    let mut bytes = bytes.as_slice();
+
+    while let Some((input, rest)) = split_record(bytes)
+        && let Some((output, rest)) = split_record(rest)
+    {
+        calls.push(RecordedCall {
+            input: input.to_vec(),
+            output: output.to_vec(),
+        });
+
+        bytes = rest;
+    }
+
+    // The recording process may have exited in the middle of a write.
+    if !bytes.is_empty() {
+        log::warn!("ignoring a truncated call in the recording of {name}");
+    }
+
+    Ok(calls)
+}
+
+fn split_record(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (len, rest) = bytes.split_first_chunk::<8>()?;
+    rest.split_at_checked(u64::from_le_bytes(*len) as usize)
+}
@@ -0,0 +1,870 @@
+//! Descriptions of the inputs and outputs of `checked` functions, compared between builds.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Write as _},
+    panic::{self, AssertUnwindSafe},
+    sync::{Mutex, PoisonError},
+};
+
+use serde::{
+    Serialize, Serializer,
+    de::DeserializeOwned,
+    ser::{
+        Error as _, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
+        SerializeTuple, SerializeTupleStruct, SerializeTupleVariant,
+    },
+};
+use serde_reflection::{
+    ContainerFormat, Error, Format, FormatHolder, Named, Registry, Samples, Tracer, TracerConfig,
+    VariantFormat,
+};
+use xxhash_rust::xxh3::xxh3_128;
+
+use crate::{abi::boxed::BoxedSlice, codec::HotpatchCodec};
+
+/// The schema of a `checked` function: its input tuple, its output, the containers they
+/// refer to by name, and whether its codec encodes fields and variants by name.
+pub type Schema = (Format, Format, Registry, bool);
+
+/// A change between the schemas of two builds of a function.
+pub struct Change {
+    pub description: String,
+    /// Whether the values that either build serializes can still be deserialized by the
+    /// other, provided that added and removed fields have defaults.
+    pub compatible: bool,
+}
+
+/// Traces the serialized form of the input tuple `I` and output `O` of a `checked` function
+/// that uses the codec `C`.
+pub fn describe_schema<I: DeserializeOwned, O: DeserializeOwned, C: HotpatchCodec>()
+-> BoxedSlice<u8> {
+    // Custom `Deserialize` implementations may panic on the values that tracing produces.
+    let schema = panic::catch_unwind(AssertUnwindSafe(|| {
+        let (input, mut registry) = trace::<I>()?;
+        let (output, output_registry) = trace::<O>()?;
+        registry.extend(output_registry);
+
+        Ok::<_, Error>((input, output, registry, C::encodes_names()))
+    }));
+
+    match schema {
+        Ok(Ok(schema)) => BoxedSlice::new(&rmp_serde::to_vec(&schema).unwrap_or_default()),
+        Ok(Err(e)) => {
+            log::debug!("error tracing a checked function schema: {e}");
+            BoxedSlice::new(&[])
+        }
+        Err(_) => BoxedSlice::new(&[]),
+    }
+}
+
+/// Hashes the serialized form of `T`, which tells apart types whose fields changed even if
+/// their layout did not.
+///
+/// Returns `None` if `T` could not be traced.
+pub fn hash_schema<T: DeserializeOwned>() -> Option<u128> {
+    let traced = panic::catch_unwind(trace::<T>).ok()?.ok()?;
+    let bytes = rmp_serde::to_vec(&traced).ok()?;
+
+    Some(xxh3_128(&bytes))
+}
+
+/// Reads a schema written by [`describe_schema`], in any generation.
+pub fn read(bytes: &[u8]) -> Option<Schema> {
+    rmp_serde::from_slice(bytes).ok()
+}
+
+/// Checks that samples of the input (or the output) of `schema`, serialized with `C`,
+/// deserialize into `I` (or `O`).
+///
+/// Samples hold zeroes and empty strings, so types that reject them fail to deserialize
+/// samples of their own schema too.
+pub fn probe_schema<I: DeserializeOwned, O: DeserializeOwned, C: HotpatchCodec>(
+    schema: &[u8],
+    output: bool,
+) -> bool {
+    let Some((input_format, output_format, registry, _)) = read(schema) else {
+        return false;
+    };
+
+    let format = if output {
+        &output_format
+    } else {
+        &input_format
+    };
+
+    // Every variant of an enum is sampled by one of the samples.
+    let samples = registry
+        .values()
+        .map(|container| match container {
+            ContainerFormat::Enum(variants) => variants.len(),
+            _ => 1,
+        })
+        .max()
+        .unwrap_or(1);
+
+    let probe = |variant| {
+        let sample = Sample {
+            format,
+            registry: &registry,
+            variant,
+            depth: 0,
+        };
+
+        let mut bytes = vec![];
+        C::serialize(&sample, &mut bytes)?;
+
+        if output {
+            C::deserialize::<O>(&bytes).map(drop)
+        } else {
+            C::deserialize::<I>(&bytes).map(drop)
+        }
+    };
+
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        (0..samples).all(|variant| {
+            probe(variant)
+                .inspect_err(|e| log::debug!("schema sample failed to deserialize: {e}"))
+                .is_ok()
+        })
+    }))
+    .unwrap_or(false)
+}
+
+fn trace<T: DeserializeOwned>() -> Result<(Format, Registry), Error> {
+    // How many times enums found incomplete by a previous attempt are traced again.
+    const MAX_ATTEMPTS: usize = 8;
+    const MAX_TRACES: usize = 64;
+
+    let samples = Samples::new();
+    let mut enums = BTreeSet::new();
+
+    for _ in 0..MAX_ATTEMPTS {
+        let mut tracer = Tracer::new(TracerConfig::default());
+        let mut format = Format::unknown();
+
+        for _ in 0..MAX_TRACES {
+            format = tracer.trace_type_once::<T>(&samples)?.0;
+
+            if let Format::TypeName(name) = &format {
+                enums.insert(name.clone());
+            }
+
+            // Clearing an incomplete enum lets the next trace visit its other variants.
+            let incomplete = enums
+                .iter()
+                .filter(|name| tracer.check_incomplete_enum(name).is_some())
+                .count();
+
+            if incomplete == 0 {
+                break;
+            }
+        }
+
+        match tracer.registry() {
+            Ok(registry) => {
+                format.normalize()?;
+                return Ok((format, registry));
+            }
+            Err(Error::MissingVariants(names)) => enums.extend(names),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(Error::MissingVariants(enums.into_iter().collect()))
+}
+
+/// Describes how the schema of a function changed between `old`, the schema of its callers,
+/// and `new`.
+pub fn diff(old: &Schema, new: &Schema) -> Vec<Change> {
+    let (old_input, old_output, old_registry, old_names) = old;
+    let (new_input, new_output, new_registry, new_names) = new;
+
+    let mut diff = Diff {
+        encodes_names: *old_names && *new_names,
+        inputs: container_names(old_input, old_registry),
+        outputs: container_names(old_output, old_registry),
+        changes: vec![],
+    };
+    diff.inputs.extend(container_names(new_input, new_registry));
+    diff.outputs
+        .extend(container_names(new_output, new_registry));
+
+    let old_args = elements(old_input);
+    let new_args = elements(new_input);
+
+    if old_args.len() != new_args.len() {
+        diff.push(
+            false,
+            format!(
+                "arguments changed from {} to {}",
+                Display(old_input),
+                Display(new_input)
+            ),
+        );
+    } else {
+        for (i, (old_arg, new_arg)) in old_args.iter().zip(&new_args).enumerate() {
+            if old_arg != new_arg {
+                diff.push(
+                    false,
+                    format!(
+                        "argument {} changed from {} to {}",
+                        i + 1,
+                        Display(old_arg),
+                        Display(new_arg)
+                    ),
+                );
+            }
+        }
+    }
+
+    if old_output != new_output {
+        diff.push(
+            false,
+            format!(
+                "output changed from {} to {}",
+                Display(old_output),
+                Display(new_output)
+            ),
+        );
+    }
+
+    for (name, old_container) in old_registry {
+        if let Some(new_container) = new_registry.get(name) {
+            diff.container(name, old_container, new_container);
+        }
+    }
+
+    diff.changes
+}
+
+struct Diff {
+    encodes_names: bool,
+    /// Containers that are part of the input, sent by callers to the new build.
+    inputs: BTreeSet<String>,
+    /// Containers that are part of the output, sent by the new build to callers.
+    outputs: BTreeSet<String>,
+    changes: Vec<Change>,
+}
+
+impl Diff {
+    fn push(&mut self, compatible: bool, description: String) {
+        self.changes.push(Change {
+            description,
+            compatible,
+        });
+    }
+
+    fn container(&mut self, name: &str, old: &ContainerFormat, new: &ContainerFormat) {
+        match (old, new) {
+            (ContainerFormat::Struct(old_fields), ContainerFormat::Struct(new_fields)) => {
+                self.fields(&format!("struct {name}"), old_fields, new_fields);
+            }
+            (ContainerFormat::Enum(old_variants), ContainerFormat::Enum(new_variants)) => {
+                let old_variants = by_name(old_variants);
+                let new_variants = by_name(new_variants);
+
+                for (variant, (old_index, old_variant)) in &old_variants {
+                    let Some((new_index, new_variant)) = new_variants.get(variant) else {
+                        // Callers may still send a removed variant.
+                        let compatible = !self.inputs.contains(name);
+                        self.push(
+                            compatible,
+                            format!("enum {name}: variant `{variant}` removed"),
+                        );
+                        continue;
+                    };
+
+                    let path = format!("enum {name}: variant `{variant}`");
+
+                    if old_index != new_index {
+                        self.push(
+                            self.encodes_names,
+                            format!("{path} moved from index {old_index} to {new_index}"),
+                        );
+                    }
+
+                    match (old_variant, new_variant) {
+                        (VariantFormat::Struct(old_fields), VariantFormat::Struct(new_fields)) => {
+                            self.fields(&path, old_fields, new_fields);
+                        }
+                        (old_variant, new_variant) if old_variant != new_variant => self.push(
+                            false,
+                            format!(
+                                "{path} changed from {} to {}",
+                                Display(*old_variant),
+                                Display(*new_variant)
+                            ),
+                        ),
+                        _ => {}
+                    }
+                }
+
+                for variant in new_variants.keys() {
+                    if !old_variants.contains_key(variant) {
+                        // Callers can not receive an added variant.
+                        let compatible = !self.outputs.contains(name);
+                        self.push(
+                            compatible,
+                            format!("enum {name}: variant `{variant}` added"),
+                        );
+                    }
+                }
+            }
+            (old, new) if old != new => self.push(
+                false,
+                format!(
+                    "{} {name} changed from {} to {}",
+                    kind(old),
+                    Display(old),
+                    Display(new)
+                ),
+            ),
+            _ => {}
+        }
+    }
+
+    fn fields(&mut self, path: &str, old_fields: &[Named<Format>], new_fields: &[Named<Format>]) {
+        for old_field in old_fields {
+            match new_fields.iter().find(|field| field.name == old_field.name) {
+                None => self.push(
+                    self.encodes_names,
+                    format!("{path}: field `{}` removed", old_field.name),
+                ),
+                Some(new_field) if new_field.value != old_field.value => self.push(
+                    false,
+                    format!(
+                        "{path}: field `{}` changed from {} to {}",
+                        old_field.name,
+                        Display(&old_field.value),
+                        Display(&new_field.value)
+                    ),
+                ),
+                Some(_) => {}
+            }
+        }
+
+        for new_field in new_fields {
+            if !old_fields.iter().any(|field| field.name == new_field.name) {
+                self.push(
+                    self.encodes_names,
+                    format!("{path}: field `{}` added", new_field.name),
+                );
+            }
+        }
+
+        let old_order = old_fields.iter().map(|field| &field.name);
+        let new_order = new_fields
+            .iter()
+            .map(|field| &field.name)
+            .filter(|name| old_fields.iter().any(|field| &field.name == *name));
+
+        if !old_order
+            .filter(|name| new_fields.iter().any(|field| &field.name == *name))
+            .eq(new_order)
+        {
+            self.push(self.encodes_names, format!("{path}: fields reordered"));
+        }
+    }
+}
+
+fn by_name(
+    variants: &BTreeMap<u32, Named<VariantFormat>>,
+) -> BTreeMap<&str, (u32, &VariantFormat)> {
+    variants
+        .iter()
+        .map(|(index, variant)| (variant.name.as_str(), (*index, &variant.value)))
+        .collect()
+}
+
+/// The names of the containers that `format` refers to, directly or through other containers.
+fn container_names(format: &Format, registry: &Registry) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    let mut pending = type_names(format);
+
+    while let Some(name) = pending.pop() {
+        if let Some(container) = registry.get(&name)
+            && !names.contains(&name)
+        {
+            pending.extend(type_names(container));
+        }
+
+        names.insert(name);
+    }
+
+    names
+}
+
+fn type_names(holder: &impl FormatHolder) -> Vec<String> {
+    let mut names = vec![];
+
+    let _ = holder.visit(&mut |format| {
+        if let Format::TypeName(name) = format {
+            names.push(name.clone());
+        }
+        Ok(())
+    });
+
+    names
+}
+
+/// The elements of a tuple, which may have been compressed into an array.
+fn elements(format: &Format) -> Vec<Format> {
+    match format {
+        Format::Unit => vec![],
+        Format::Tuple(formats) => formats.clone(),
+        Format::TupleArray { content, size } => vec![(**content).clone(); *size],
+        format => vec![format.clone()],
+    }
+}
+
+fn kind(container: &ContainerFormat) -> &'static str {
+    match container {
+        ContainerFormat::Enum(_) => "enum",
+        _ => "struct",
+    }
+}
+
+/// A value in the shape of a format of a schema, serialized by [`probe_schema`].
+///
+/// Numbers are zero, strings are empty, options, sequences and maps hold one element, and
+/// enums take their `variant`-th variant, or their last one. Past `MAX_DEPTH`, options and
+/// collections are empty and enums take their first variant, to end recursive types.
+struct Sample<'a> {
+    format: &'a Format,
+    registry: &'a Registry,
+    variant: usize,
+    depth: usize,
+}
+
+impl<'a> Sample<'a> {
+    const MAX_DEPTH: usize = 16;
+
+    fn nested(&self, format: &'a Format) -> Self {
+        Sample {
+            format,
+            registry: self.registry,
+            variant: self.variant,
+            depth: self.depth + 1,
+        }
+    }
+
+    fn is_deep(&self) -> bool {
+        self.depth >= Self::MAX_DEPTH
+    }
+
+    fn serialize_container<S: Serializer>(
+        &self,
+        name: &str,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let Some(container) = self.registry.get(name) else {
+            return Err(S::Error::custom(format!("unknown type {name}")));
+        };
+
+        let name = intern(name);
+
+        match container {
+            ContainerFormat::UnitStruct => serializer.serialize_unit_struct(name),
+            ContainerFormat::NewTypeStruct(format) => {
+                serializer.serialize_newtype_struct(name, &self.nested(format))
+            }
+            ContainerFormat::TupleStruct(formats) => {
+                let mut tuple = serializer.serialize_tuple_struct(name, formats.len())?;
+                for format in formats {
+                    tuple.serialize_field(&self.nested(format))?;
+                }
+                tuple.end()
+            }
+            ContainerFormat::Struct(fields) => {
+                let mut fields_serializer = serializer.serialize_struct(name, fields.len())?;
+                for field in fields {
+                    fields_serializer
+                        .serialize_field(intern(&field.name), &self.nested(&field.value))?;
+                }
+                fields_serializer.end()
+            }
+            ContainerFormat::Enum(variants) => {
+                let variant = if self.is_deep() { 0 } else { self.variant };
+
+                let Some((index, variant)) = variants
+                    .iter()
+                    .nth(variant.min(variants.len().saturating_sub(1)))
+                else {
+                    return Err(S::Error::custom(format!("enum {name} has no variants")));
+                };
+
+                let variant_name = intern(&variant.name);
+
+                match &variant.value {
+                    VariantFormat::Variable(_) => {
+                        Err(S::Error::custom(format!("enum {name} is incomplete")))
+                    }
+                    VariantFormat::Unit => {
+                        serializer.serialize_unit_variant(name, *index, variant_name)
+                    }
+                    VariantFormat::NewType(format) => serializer.serialize_newtype_variant(
+                        name,
+                        *index,
+                        variant_name,
+                        &self.nested(format),
+                    ),
+                    VariantFormat::Tuple(formats) => {
+                        let mut tuple = serializer.serialize_tuple_variant(
+                            name,
+                            *index,
+                            variant_name,
+                            formats.len(),
+                        )?;
+                        for format in formats {
+                            tuple.serialize_field(&self.nested(format))?;
+                        }
+                        tuple.end()
+                    }
+                    VariantFormat::Struct(fields) => {
+                        let mut fields_serializer = serializer.serialize_struct_variant(
+                            name,
+                            *index,
+                            variant_name,
+                            fields.len(),
+                        )?;
+                        for field in fields {
+                            fields_serializer
+                                .serialize_field(intern(&field.name), &self.nested(&field.value))?;
+                        }
+                        fields_serializer.end()
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Serialize for Sample<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.depth > 2 * Self::MAX_DEPTH {
+            return Err(S::Error::custom("the schema is nested too deeply"));
+        }
+
+        match self.format {
+            Format::Variable(_) => Err(S::Error::custom("the schema is incomplete")),
+            Format::TypeName(name) => self.serialize_container(name, serializer),
+            Format::Unit => serializer.serialize_unit(),
+            Format::Bool => serializer.serialize_bool(false),
+            Format::I8 => serializer.serialize_i8(0),
+            Format::I16 => serializer.serialize_i16(0),
+            Format::I32 => serializer.serialize_i32(0),
+            Format::I64 => serializer.serialize_i64(0),
+            Format::I128 => serializer.serialize_i128(0),
+            Format::U8 => serializer.serialize_u8(0),
+            Format::U16 => serializer.serialize_u16(0),
+            Format::U32 => serializer.serialize_u32(0),
+            Format::U64 => serializer.serialize_u64(0),
+            Format::U128 => serializer.serialize_u128(0),
+            Format::F32 => serializer.serialize_f32(0.0),
+            Format::F64 => serializer.serialize_f64(0.0),
+            Format::Char => serializer.serialize_char('\0'),
+            Format::Str => serializer.serialize_str(""),
+            Format::Bytes => serializer.serialize_bytes(&[]),
+            Format::Option(_) if self.is_deep() => serializer.serialize_none(),
+            Format::Option(format) => serializer.serialize_some(&self.nested(format)),
+            Format::Seq(format) => {
+                let len = if self.is_deep() { 0 } else { 1 };
+                let mut seq = serializer.serialize_seq(Some(len))?;
+                for _ in 0..len {
+                    seq.serialize_element(&self.nested(format))?;
+                }
+                seq.end()
+            }
+            Format::Map { key, value } => {
+                let len = if self.is_deep() { 0 } else { 1 };
+                let mut map = serializer.serialize_map(Some(len))?;
+                for _ in 0..len {
+                    map.serialize_entry(&self.nested(key), &self.nested(value))?;
+                }
+                map.end()
+            }
+            Format::Tuple(formats) => {
+                let mut tuple = serializer.serialize_tuple(formats.len())?;
+                for format in formats {
+                    tuple.serialize_element(&self.nested(format))?;
+                }
+                tuple.end()
+            }
+            Format::TupleArray { content, size } => {
+                let mut tuple = serializer.serialize_tuple(*size)?;
+                for _ in 0..*size {
+                    tuple.serialize_element(&self.nested(content))?;
+                }
+                tuple.end()
+            }
+        }
+    }
+}
+
+/// Serde requires the names of structs, fields and variants to be `'static`, so the names
+/// of samples are leaked, once each.
+fn intern(name: &str) -> &'static str {
+    static NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+    let mut names = NAMES.lock().unwrap_or_else(PoisonError::into_inner);
+
+    if let Some(name) = names.get(name) {
+        return name;
+    }
+
+    let name: &'static str = Box::leak(name.into());
+    names.insert(name);
+    name
+}
+
+/// Displays formats like the Rust types they were traced from.
+struct Display<T>(T);
+
+impl fmt::Display for Display<&Format> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.0 {
+            Format::Variable(_) => "_",
+            Format::TypeName(name) => name,
+            Format::Unit => "()",
+            Format::Bool => "bool",
+            Format::I8 => "i8",
+            Format::I16 => "i16",
+            Format::I32 => "i32",
+            Format::I64 => "i64",
+            Format::I128 => "i128",
+            Format::U8 => "u8",
+            Format::U16 => "u16",
+            Format::U32 => "u32",
+            Format::U64 => "u64",
+            Format::U128 => "u128",
+            Format::F32 => "f32",
+            Format::F64 => "f64",
+            Format::Char => "char",
+            Format::Str => "String",
+            Format::Bytes => "bytes",
+            Format::Option(format) => return write!(f, "Option<{}>", Display(&**format)),
+            Format::Seq(format) => return write!(f, "Vec<{}>", Display(&**format)),
+            Format::Map { key, value } => {
+                return write!(f, "Map<{}, {}>", Display(&**key), Display(&**value));
+            }
+            Format::Tuple(formats) => return write_tuple(f, formats),
+            Format::TupleArray { content, size } => {
+                return write!(f, "[{}; {size}]", Display(&**content));
+            }
+        };
+
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for Display<&VariantFormat> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            VariantFormat::Variable(_) => f.write_str("_"),
+            VariantFormat::Unit => f.write_str("a unit variant"),
+            VariantFormat::NewType(format) => write_tuple(f, std::slice::from_ref(&**format)),
+            VariantFormat::Tuple(formats) => write_tuple(f, formats),
+            VariantFormat::Struct(fields) => write_fields(f, fields),
+        }
+    }
+}
+
+impl fmt::Display for Display<&ContainerFormat> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            ContainerFormat::UnitStruct => f.write_str("a unit struct"),
+            ContainerFormat::NewTypeStruct(format) => {
+                write_tuple(f, std::slice::from_ref(&**format))
+            }
+            ContainerFormat::TupleStruct(formats) => write_tuple(f, formats),
+            ContainerFormat::Struct(fields) => write_fields(f, fields),
+            ContainerFormat::Enum(variants) => {
+                let mut names = String::new();
+
+                for (i, variant) in variants.values().enumerate() {
+                    if i != 0 {
+                        names.push_str(" | ");
+                    }
+                    let _ = write!(names, "{}", variant.name);
+                }
+
+                write!(f, "{{ {names} }}")
+            }
+        }
+    }
+}
+
+fn write_tuple(f: &mut fmt::Formatter<'_>, formats: &[Format]) -> fmt::Result {
+    f.write_str("(")?;
+
+    for (i, format) in formats.iter().enumerate() {
+        if i != 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{}", Display(format))?;
+    }
+
+    f.write_str(")")
+}
+
+fn write_fields(f: &mut fmt::Formatter<'_>, fields: &[Named<Format>]) -> fmt::Result {
+    f.write_str("{ ")?;
+
+    for (i, field) in fields.iter().enumerate() {
+        if i != 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{}: {}", field.name, Display(&field.value))?;
+    }
+
+    f.write_str(" }")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    // Fields are only traced.
+    #[allow(dead_code)]
+    mod old {
+        use super::*;
+
+        #[derive(Deserialize)]
+        pub struct Player {
+            pub hp: i32,
+            pub name: String,
+        }
+
+        #[derive(Deserialize)]
+        pub enum Command {
+            Jump,
+            Crouch,
+        }
+    }
+
+    #[allow(dead_code)]
+    mod new {
+        use super::*;
+
+        #[derive(Deserialize)]
+        pub struct Player {
+            pub hp: f32,
+            pub mana: u32,
+        }
+
+        #[derive(Deserialize)]
+        pub enum Command {
+            Jump,
+            Dash,
+        }
+    }
+
+    fn schema<I: DeserializeOwned, O: DeserializeOwned>(encodes_names: bool) -> Schema {
+        let (input, mut registry) = trace::<I>().unwrap();
+        let (output, output_registry) = trace::<O>().unwrap();
+        registry.extend(output_registry);
+
+        (input, output, registry, encodes_names)
+    }
+
+    fn changes(old: &Schema, new: &Schema) -> Vec<(String, bool)> {
+        diff(old, new)
+            .into_iter()
+            .map(|change| (change.description, change.compatible))
+            .collect()
+    }
+
+    #[test]
+    fn diff_struct_fields() {
+        let old = schema::<(old::Player,), ()>(true);
+        let new = schema::<(new::Player,), ()>(true);
+
+        assert_eq!(
+            changes(&old, &new),
+            [
+                (
+                    "struct Player: field `hp` changed from i32 to f32".into(),
+                    false
+                ),
+                ("struct Player: field `name` removed".into(), true),
+                ("struct Player: field `mana` added".into(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_positional_struct_fields() {
+        let old = schema::<(old::Player,), ()>(false);
+        let new = schema::<(new::Player,), ()>(false);
+
+        assert_eq!(
+            changes(&old, &new),
+            [
+                (
+                    "struct Player: field `hp` changed from i32 to f32".into(),
+                    false
+                ),
+                ("struct Player: field `name` removed".into(), false),
+                ("struct Player: field `mana` added".into(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_input_variants() {
+        let old = schema::<(old::Command,), ()>(true);
+        let new = schema::<(new::Command,), ()>(true);
+
+        assert_eq!(
+            changes(&old, &new),
+            [
+                ("enum Command: variant `Crouch` removed".into(), false),
+                ("enum Command: variant `Dash` added".into(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_output_variants() {
+        let old = schema::<(), old::Command>(true);
+        let new = schema::<(), new::Command>(true);
+
+        assert_eq!(
+            changes(&old, &new),
+            [
+                ("enum Command: variant `Crouch` removed".into(), true),
+                ("enum Command: variant `Dash` added".into(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_arguments() {
+        let old = schema::<(u32, Vec<i8>), Option<u8>>(true);
+        let new = schema::<(String, Vec<i8>), Option<u16>>(true);
+
+        assert_eq!(
+            changes(&old, &new),
+            [
+                ("argument 1 changed from u32 to String".into(), false),
+                (
+                    "output changed from Option<u8> to Option<u16>".into(),
+                    false
+                ),
+            ]
+        );
+
+        let fewer = schema::<(u32,), Option<u8>>(true);
+
+        assert_eq!(
+            changes(&old, &fewer),
+            [(
+                "arguments changed from (u32, Vec<i8>) to (u32)".into(),
+                false
+            )]
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use tempfile::TempDir;
+
+use crate::{TARGET_DIR, config::Config, os::process_exists};
+
+struct ScratchDir {
+    path: PathBuf,
+    pid: u32,
+    modified: SystemTime,
+    size: u64,
+}
+
+pub fn scratch_path() -> String {
+    format!("{TARGET_DIR}/.hotpatch")
+}
+
+pub fn create_scratch_dir() -> io::Result<()> {
+    match fs::create_dir(scratch_path()) {
+        Err(e) if e.kind() != io::ErrorKind::AlreadyExists => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Creates a temporary directory in the scratch area, owned by the current process.
+///
+/// The directory name is prefixed with the process id, which [`collect_garbage`] uses to
+/// tell whether its owner is still alive.
+pub fn temp_dir() -> io::Result<TempDir> {
+    let pid = std::process::id();
+
+    tempfile::Builder::new()
+        .prefix(&format!("{pid}-"))
+        .tempdir_in(scratch_path())
+}
+
+/// Removes scratch directories and lock files left behind by processes that no longer exist,
+/// then enforces the configured scratch area limits by removing this process' oldest
+/// scratch directories.
+pub fn collect_garbage() {
+    if let Err(e) = try_collect_garbage(Config::get()) {
+        log::warn!("error collecting stale hot-patch files: {e}");
+    }
+}
+
+fn try_collect_garbage(config: &Config) -> io::Result<()> {
+    let own_pid = std::process::id();
+
+    let mut scratch_dirs = vec![];
+
+    for entry in fs::read_dir(scratch_path())? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+
+        let file_name = entry.file_name();
+        let Some(file_name) = file_name.to_str() else {
+            continue;
+        };
+
+        if file_type.is_file()
+            && let Some(pid) = file_name.strip_suffix(".lock").and_then(parse_pid)
+        {
+            if pid != own_pid && !process_exists(pid) {
+                log::debug!("removing stale lock file {path:?}");
+                let _ = fs::remove_file(&path);
+            }
+        } else if file_type.is_dir()
+            && let Some(pid) = file_name
+                .split_once('-')
+                .and_then(|(pid, _)| parse_pid(pid))
+        {
+            if pid != own_pid && !process_exists(pid) {
+                log::debug!("removing stale scratch directory {path:?}");
+                let _ = fs::remove_dir_all(&path);
+                continue;
+            }
+
+            scratch_dirs.push(ScratchDir {
+                modified: entry.metadata()?.modified()?,
+                size: dir_size(&path),
+                path,
+                pid,
+            });
+        }
+    }
+
+    let mut count = scratch_dirs.len();
+    let mut size = scratch_dirs.iter().map(|dir| dir.size).sum::<u64>();
+
+    let over_limit = |count: usize, size: u64| {
+        config.scratch_max_count.is_some_and(|max| count > max)
+            || config.scratch_max_bytes.is_some_and(|max| size > max)
+    };
+
+    // Directories of other live processes may be in use, only evict our own.
+    scratch_dirs.retain(|dir| dir.pid == own_pid);
+    scratch_dirs.sort_by_key(|dir| dir.modified);
+
+    for dir in scratch_dirs {
+        if !over_limit(count, size) {
+            break;
+        }
+
+        log::debug!("evicting scratch directory {:?}", dir.path);
+
+        // Fails for libraries that are still loaded on Windows.
+        if fs::remove_dir_all(&dir.path).is_ok() {
+            count -= 1;
+            size -= dir.size;
+        }
+    }
+
+    if over_limit(count, size) {
+        log::warn!("hot-patch scratch area is over its limits ({count} directories, {size} bytes)");
+    }
+
+    Ok(())
+}
+
+fn parse_pid(pid: &str) -> Option<u32> {
+    pid.parse().ok()
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+
+    entries
+        .filter_map(Result::ok)
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => dir_size(&entry.path()),
+            Ok(_) => entry.metadata().map_or(0, |metadata| metadata.len()),
+            Err(_) => 0,
+        })
+        .sum()
+}
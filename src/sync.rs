@@ -0,0 +1,86 @@
+//! Synchronization primitives whose state is shared by every generation of the library.
+//!
+//! Statics of a hot-patched build are separate from those of the build that was originally
+//! loaded, so a [`Once`] or [`LazyLock`](std::sync::LazyLock) runs its initialization again
+//! in every generation. The types of this module keep their state in the original library
+//! instead, like the statics of [`persistent!`](crate::persistent), so that initialization
+//! runs exactly once per process. Their state is keyed by a name, which must be unique and
+//! should be kept between builds:
+//!
+//! ```
+//! use libhotpatch::sync::{HotLazy, HotOnce};
+//!
+//! static INIT: HotOnce = HotOnce::new(concat!(module_path!(), "::INIT"));
+//! static LIMIT: HotLazy<u32> = HotLazy::new(concat!(module_path!(), "::LIMIT"), || 16);
+//!
+//! INIT.call_once(|| println!("initialized"));
+//! assert_eq!(*LIMIT, 16);
+//! ```
+//!
+//! The initialization runs in the generation that first reaches it, and affects the statics
+//! of that generation only. Values are never dropped, with the same restrictions as the
+//! values of [`persistent!`](crate::persistent).
+
+use std::{
+    ops::Deref,
+    sync::{Once, OnceLock},
+};
+
+use crate::persistent::Persistent;
+
+/// A [`Once`] that completes once, shared by every generation.
+pub struct HotOnce {
+    once: Persistent<Once>,
+}
+
+/// A lazily initialized value that is initialized once and shared by every generation.
+pub struct HotLazy<T, F = fn() -> T> {
+    cell: Persistent<OnceLock<T>>,
+    init: F,
+}
+
+impl HotOnce {
+    /// Creates a `HotOnce` whose state is shared by the statics with the same `name`.
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            once: Persistent::new(name, Once::new),
+        }
+    }
+
+    /// Runs `f` if no generation has completed a call yet, see [`Once::call_once`].
+    pub fn call_once(&self, f: impl FnOnce()) {
+        self.once.call_once(f);
+    }
+
+    /// Returns whether a generation has completed a call.
+    pub fn is_completed(&self) -> bool {
+        self.once.is_completed()
+    }
+}
+
+impl<T, F> HotLazy<T, F> {
+    /// Creates a `HotLazy` whose value is shared by the statics with the same `name` and
+    /// type.
+    pub const fn new(name: &'static str, init: F) -> Self {
+        Self {
+            cell: Persistent::new(name, OnceLock::new),
+            init,
+        }
+    }
+}
+
+impl<T: Send + Sync, F: Fn() -> T> HotLazy<T, F> {
+    /// Returns the value, initializing it with this generation's `init` if no generation has
+    /// yet.
+    pub fn force(this: &Self) -> &T {
+        this.cell.get_or_init(&this.init)
+    }
+}
+
+impl<T: Send + Sync, F: Fn() -> T> Deref for HotLazy<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        HotLazy::force(self)
+    }
+}
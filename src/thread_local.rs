@@ -0,0 +1,106 @@
+//! Thread-locals that keep their value when the library is hot-patched, see
+//! [`hot_thread_local!`].
+
+use std::ptr;
+
+use crate::{
+    persistent::{Registry, drop_value},
+    watcher::Watcher,
+};
+
+/// Declares thread-locals whose values are shared by every generation of the library.
+///
+/// ```
+/// use std::cell::Cell;
+///
+/// libhotpatch::hot_thread_local! {
+///     static SEED: Cell<u64> = Cell::new(1);
+/// }
+///
+/// SEED.with(|seed| seed.set(seed.get() * 6364136223846793005 + 1));
+/// ```
+///
+/// The values of every thread are kept in the thread-local storage of the library that was
+/// originally loaded, instead of that of the generation that declares them, and are keyed
+/// like the statics of [`persistent!`](crate::persistent). When the type of a thread-local
+/// changes, new generations get a new value on every thread.
+///
+/// Values are dropped when their thread exits, by the generation that created them, which
+/// stays loaded since builds are never unloaded.
+#[macro_export]
+macro_rules! hot_thread_local {
+    (
+        $(#[$attr:meta])*
+        $vis:vis static $name:ident: $ty:ty = $init:expr; $($rest:tt)*
+    ) => {
+        $(#[$attr])*
+        $vis static $name: $crate::HotThreadLocal<$ty> = $crate::HotThreadLocal::new(
+            ::std::concat!(::std::module_path!(), "::", ::std::stringify!($name)),
+            || $init,
+        );
+
+        $crate::hot_thread_local!($($rest)*);
+    };
+    () => {};
+}
+
+/// A thread-local declared with [`hot_thread_local!`].
+pub struct HotThreadLocal<T> {
+    name: &'static str,
+    init: fn() -> T,
+}
+
+/// The thread-locals of the current thread, which are dropped when it exits.
+struct ThreadRegistry(Registry);
+
+impl<T> HotThreadLocal<T> {
+    #[doc(hidden)]
+    pub const fn new(name: &'static str, init: fn() -> T) -> Self {
+        Self { name, init }
+    }
+
+    /// Calls `f` with the value of the current thread, initializing it if no generation has
+    /// on this thread yet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the thread-locals of the original library were already destroyed.
+    pub fn with<R>(&'static self, f: impl FnOnce(&T) -> R) -> R {
+        let registry = Watcher::get().map_or_else(|| current_registry(), Watcher::thread_locals);
+
+        // SAFETY: the registry is valid until the current thread exits.
+        let registry = unsafe { registry.as_ref() }
+            .expect("cannot access a hot thread-local during or after destruction");
+
+        let value = registry.get_or_insert(self.name, 0, None, Some(drop_value::<T>), |previous| {
+            if previous.is_some() {
+                let name = self.name;
+                log::warn!("the type of hot thread-local {name} changed, it is reset");
+            }
+
+            (self.init)()
+        });
+
+        // SAFETY: values are only dropped when the current thread exits, and are only shared
+        // between thread-locals with the same name and type fingerprint.
+        f(unsafe { &*value })
+    }
+}
+
+/// Returns the thread-locals of the current thread, or null if they were destroyed.
+pub extern "C" fn current_registry() -> *const Registry {
+    thread_local! {
+        static REGISTRY: ThreadRegistry = const { ThreadRegistry(Registry::new()) };
+    }
+
+    REGISTRY
+        .try_with(|registry| ptr::from_ref(&registry.0))
+        .unwrap_or(ptr::null())
+}
+
+impl Drop for ThreadRegistry {
+    fn drop(&mut self) {
+        // SAFETY: the registry can no longer be resolved by `current_registry`.
+        unsafe { self.0.clear() };
+    }
+}
@@ -1,39 +1,65 @@
 use std::{
-    fs::{self, File},
-    io,
+    error::Error,
+    fmt,
+    fs::{self, File, Metadata},
+    io::{self, Read},
+    path::PathBuf,
     sync::{
         OnceLock,
-        atomic::{AtomicU32, AtomicU64, Ordering as AtomicOrdering},
+        atomic::{AtomicI32, AtomicU32, AtomicU64, Ordering as AtomicOrdering},
     },
     time::{Duration, Instant, UNIX_EPOCH},
 };
 
 use atomic_wait::{wait, wake_all};
+use tempfile::TempDir;
 use xxhash_rust::xxh3::xxh3_64;
 
+#[cfg(target_os = "linux")]
+use crate::hotpatch::validate_fn_table;
 use crate::{
     TARGET_DIR,
     abi::{
         str::Str,
         time::{AtomicDuration, AtomicInstant},
     },
-    hotpatch::update_fn_table,
+    config::{Config, Loader, Replay},
+    hotpatch::{
+        ABI_MARKER, HotpatchSlot, find_slot, replay_fn_table, run_canaries, update_fn_table,
+    },
     lock::HotpatchLock,
-    os::Module,
+    os::{self, Module},
+    persistent::Registry,
+    scratch,
+    thread_local::current_registry,
 };
+#[cfg(feature = "checked")]
+use crate::{config::SchemaEvolution, hotpatch::diff_schemas};
 
 #[repr(C)]
 pub struct Watcher {
     last_update: AtomicInstant,
     library_modified: AtomicDuration,
     library_hash: AtomicU64,
+    rejected_hash: AtomicU64,
+    /// The lowest descriptor number the next in-memory build may be loaded from.
+    next_memfd: AtomicI32,
     library_name: Str<'static>,
     update_lock: AtomicU32,
+    pending_modified: AtomicDuration,
+    pending_len: AtomicU64,
+    pending_since: AtomicInstant,
+    persistent: Registry,
+    thread_locals: extern "C" fn() -> *const Registry,
+    find_slot: extern "C" fn(u128) -> *const HotpatchSlot,
 }
 
 impl Watcher {
     const POLL_MS: u64 = 100;
 
+    #[cfg(target_os = "linux")]
+    const VALIDATE_TIMEOUT: Duration = Duration::from_secs(10);
+
     pub fn get() -> Option<&'static Watcher> {
         *WATCHER.get_or_init(|| {
             Self::new()
@@ -42,6 +68,23 @@ impl Watcher {
         })
     }
 
+    /// The persistent statics of every generation.
+    pub fn persistent(&self) -> &Registry {
+        &self.persistent
+    }
+
+    /// The hot thread-locals of the current thread, in the original library, or null if they
+    /// were destroyed.
+    pub(crate) fn thread_locals(&self) -> *const Registry {
+        (self.thread_locals)()
+    }
+
+    /// The slot of the original library whose function hashes to `hash`.
+    pub(crate) fn find_slot(&self, hash: u128) -> Option<&'static HotpatchSlot> {
+        // SAFETY: the slots of the original library are statics.
+        unsafe { (self.find_slot)(hash).as_ref() }
+    }
+
     pub fn poll(&'static self) {
         let last_update = self.last_update.load(AtomicOrdering::Relaxed);
 
@@ -90,17 +133,12 @@ impl Watcher {
             return Err(io::Error::other("not all exports are available"));
         }
 
-        let time_modified = File::open(current_library.file_path())?
-            .metadata()?
-            .modified()?
-            .duration_since(UNIX_EPOCH)
-            .unwrap();
+        let metadata = File::open(current_library.file_path())?.metadata()?;
 
-        if let Err(e) = fs::create_dir(format!("{TARGET_DIR}/.hotpatch"))
-            && e.kind() != io::ErrorKind::AlreadyExists
-        {
-            return Err(e);
-        }
+        let time_modified = metadata.modified()?.duration_since(UNIX_EPOCH).unwrap();
+
+        scratch::create_scratch_dir()?;
+        scratch::collect_garbage();
 
         let bytes = fs::read(current_library.file_path())?;
         let hash = xxh3_64(&bytes);
@@ -116,6 +154,14 @@ impl Watcher {
             library_modified: AtomicDuration::new(time_modified),
             library_name: Str::new(Box::leak(library_name.into())),
             library_hash: AtomicU64::new(hash),
+            rejected_hash: AtomicU64::new(0),
+            next_memfd: AtomicI32::new(0),
+            pending_modified: AtomicDuration::new(time_modified),
+            pending_len: AtomicU64::new(metadata.len()),
+            pending_since: AtomicInstant::now(),
+            persistent: Registry::new(),
+            thread_locals: current_registry,
+            find_slot,
         });
 
         Ok(Box::leak(watcher))
@@ -124,10 +170,10 @@ impl Watcher {
     fn update(&'static self) -> io::Result<()> {
         let hotpatch_library_path = format!("{TARGET_DIR}/{}", self.library_name);
 
-        let hotpatch_library = File::open(&hotpatch_library_path)?;
+        let mut hotpatch_library = File::open(&hotpatch_library_path)?;
+        let hotpatch_library_metadata = hotpatch_library.metadata()?;
 
-        let hotpatch_library_modified = hotpatch_library
-            .metadata()?
+        let hotpatch_library_modified = hotpatch_library_metadata
             .modified()?
             .duration_since(UNIX_EPOCH)
             .unwrap();
@@ -138,9 +184,22 @@ impl Watcher {
             return Ok(());
         }
 
+        if !self.is_settled(hotpatch_library_modified, hotpatch_library_metadata.len()) {
+            return Ok(());
+        }
+
         log::trace!("Watcher is updating...");
 
-        let bytes = fs::read(&hotpatch_library_path)?;
+        let Some(bytes) = read_snapshot(&mut hotpatch_library, &hotpatch_library_metadata)? else {
+            log::debug!("library is being modified, retrying later");
+            return Ok(());
+        };
+
+        if let Err(e) = os::validate_library(&bytes) {
+            log::debug!("library is incomplete ({e}), retrying later");
+            return Ok(());
+        }
+
         let hotpatch_library_hash = xxh3_64(&bytes);
 
         if hotpatch_library_hash == self.library_hash.load(AtomicOrdering::Relaxed) {
@@ -152,8 +211,32 @@ impl Watcher {
             return Ok(());
         }
 
-        self.hotpatch_library(&hotpatch_library_path)
-            .inspect_err(|e| log::error!("error hot-patching library: {e}"))?;
+        if hotpatch_library_hash == self.rejected_hash.load(AtomicOrdering::Relaxed) {
+            log::trace!("file hash matched a rejected build, no update required");
+
+            self.library_modified
+                .store(hotpatch_library_modified, AtomicOrdering::Relaxed);
+
+            return Ok(());
+        }
+
+        if let Err(e) = self.hotpatch_library(&bytes) {
+            if !is_rejection(&e) {
+                log::error!("error hot-patching library, retrying later: {e}");
+                return Err(e);
+            }
+
+            log::error!("rejected build, skipping it: {e}");
+
+            // The build is not loaded again until the library changes.
+            self.library_modified
+                .store(hotpatch_library_modified, AtomicOrdering::Relaxed);
+
+            self.rejected_hash
+                .store(hotpatch_library_hash, AtomicOrdering::Relaxed);
+
+            return Err(e);
+        }
 
         self.library_modified
             .store(hotpatch_library_modified, AtomicOrdering::Relaxed);
@@ -164,46 +247,224 @@ impl Watcher {
         Ok(())
     }
 
-    fn hotpatch_library(&'static self, hotpatch_library_path: &str) -> io::Result<()> {
+    /// Returns whether the library size and modification time have not changed for the
+    /// configured debounce window, restarting the window if they did.
+    fn is_settled(&self, modified: Duration, len: u64) -> bool {
+        let debounce = Config::get().debounce;
+
+        if debounce.is_zero() {
+            return true;
+        }
+
+        let pending_modified = self.pending_modified.load(AtomicOrdering::Relaxed);
+
+        if modified.as_millis() != pending_modified.as_millis()
+            || len != self.pending_len.load(AtomicOrdering::Relaxed)
+        {
+            log::trace!("library was modified, waiting for it to settle");
+
+            self.pending_modified
+                .store(modified, AtomicOrdering::Relaxed);
+            self.pending_len.store(len, AtomicOrdering::Relaxed);
+            self.pending_since
+                .store(Instant::now(), AtomicOrdering::Relaxed);
+
+            return false;
+        }
+
+        self.pending_since.load(AtomicOrdering::Relaxed).elapsed() >= debounce
+    }
+
+    /// Loads the library image `bytes` and patches the function table with its functions.
+    ///
+    /// Fails with a [`Rejection`] if the build itself is broken, and with any other error if
+    /// loading it may succeed when retried.
+    fn hotpatch_library(&'static self, bytes: &[u8]) -> io::Result<()> {
         log::info!("hot-patching library {}", self.library_name);
 
         log::debug!("acquiring file lock");
         let _file_lock = HotpatchLock::new()?;
 
-        let temp_dir = tempfile::tempdir_in(format!("{TARGET_DIR}/.hotpatch"))?;
-        let temp_path = temp_dir.path().join(self.library_name.as_str());
+        log::debug!("inspecting library");
+        os::inspect_library(
+            bytes,
+            &[
+                "__libhotpatch_fn_table",
+                "__libhotpatch_canary_table",
+                "__libhotpatch_init_watcher",
+                ABI_MARKER,
+            ],
+        )
+        .map_err(reject)?;
+
+        let config = Config::get();
+
+        let (path, temp_dir, memfd) = match config.loader {
+            #[cfg(target_os = "linux")]
+            Loader::Memfd => self
+                .stage_in_memory(bytes)
+                .map(|(path, memfd)| (path, None, Some(memfd)))?,
+            #[cfg(not(target_os = "linux"))]
+            Loader::Memfd => {
+                log::warn!("the memfd loader is not supported, using a temporary file");
+                self.stage_in_temp_file(bytes)
+                    .map(|(path, dir)| (path, Some(dir), None))?
+            }
+            Loader::TempFile => self
+                .stage_in_temp_file(bytes)
+                .map(|(path, dir)| (path, Some(dir), None))?,
+        };
 
-        log::debug!("using temporary path {temp_path:?}");
-        fs::copy(hotpatch_library_path, &temp_path)?;
+        if config.validate_in_child {
+            #[cfg(target_os = "linux")]
+            {
+                log::debug!("validating library {path:?} in a child process");
+
+                // The child only has the forking thread, so it may deadlock on a lock another
+                // thread held, which is retried instead of rejecting the build.
+                let fns = validate_fn_table(&path, config.load_flags, Self::VALIDATE_TIMEOUT)
+                    .map_err(|e| {
+                        io::Error::new(e.kind(), format!("could not validate library: {e}"))
+                    })?
+                    .map_err(|e| reject(io::Error::other(format!("validation failed: {e}"))))?;
+
+                for (_, name) in &fns {
+                    log::trace!("validated {name}");
+                }
+            }
 
-        log::debug!("loading library {temp_path:?}");
+            #[cfg(not(target_os = "linux"))]
+            log::warn!("validation in a child process is not supported, skipping");
+        }
 
-        #[cfg(not(unix))]
-        let lib = unsafe { libloading::Library::new(&temp_path).map_err(io::Error::other)? };
+        log::debug!("loading library {path:?}");
+        let lib = os::load_library(&path, config.load_flags).map_err(reject)?;
 
-        #[cfg(unix)]
-        let lib = unsafe {
-            libloading::os::unix::Library::open(
-                Some(&temp_path),
-                libc::RTLD_LOCAL | libc::RTLD_LAZY | libc::RTLD_NODELETE,
-            )
-            .map(libloading::Library::from)
-            .map_err(io::Error::other)?
-        };
+        // The loaded library keeps its own reference to the in-memory file.
+        drop(memfd);
 
         let init_watcher = unsafe {
             lib.get::<extern "C" fn(&'static Watcher)>(b"__libhotpatch_init_watcher")
-                .map_err(io::Error::other)?
+                .map_err(|e| reject(io::Error::other(e)))?
         };
 
         log::debug!("calling __libhotpatch_init_watcher");
         init_watcher(self);
 
+        #[cfg(feature = "checked")]
+        {
+            log::debug!("comparing checked function schemas");
+            let changed = diff_schemas(&lib)?;
+
+            let rejected: Vec<_> = changed
+                .into_iter()
+                .filter(|(_, compatible)| match config.schema_evolution {
+                    SchemaEvolution::Warn => false,
+                    SchemaEvolution::Tolerant => !compatible,
+                    SchemaEvolution::Strict => true,
+                })
+                .map(|(name, _)| name)
+                .collect();
+
+            if !rejected.is_empty() {
+                return Err(reject(io::Error::other(format!(
+                    "rejected schema changes of {}",
+                    rejected.join(", ")
+                ))));
+            }
+        }
+
+        if config.replay != Replay::Off {
+            log::debug!("replaying recorded calls");
+            let diverged = replay_fn_table(&lib)?;
+
+            if config.replay == Replay::Reject && !diverged.is_empty() {
+                return Err(reject(io::Error::other(format!(
+                    "replayed calls diverged for {}",
+                    diverged.join(", ")
+                ))));
+            }
+        }
+
+        log::debug!("running canary checks");
+        run_canaries(&lib).map_err(reject)?;
+
         log::debug!("patching function table");
         update_fn_table(lib, temp_dir)?;
 
         Ok(())
     }
+
+    fn stage_in_temp_file(&self, bytes: &[u8]) -> io::Result<(PathBuf, TempDir)> {
+        scratch::collect_garbage();
+
+        let temp_dir = scratch::temp_dir()?;
+        let temp_path = temp_dir.path().join(self.library_name.as_str());
+
+        log::debug!("using temporary path {temp_path:?}");
+        fs::write(&temp_path, bytes)?;
+
+        Ok((temp_path, temp_dir))
+    }
+
+    /// Copies the library into an in-memory file, returning the descriptor path to load it
+    /// from.
+    ///
+    /// `dlopen` matches already loaded objects by path, so every build gets a descriptor
+    /// number that no previous build was loaded from, even though their descriptors are
+    /// closed.
+    #[cfg(target_os = "linux")]
+    fn stage_in_memory(&self, bytes: &[u8]) -> io::Result<(PathBuf, File)> {
+        use std::os::fd::AsRawFd;
+
+        let min_fd = self.next_memfd.load(AtomicOrdering::Relaxed);
+        let memfd = os::create_memfd(self.library_name.as_str(), bytes, min_fd)?;
+        let fd = memfd.as_raw_fd();
+
+        self.next_memfd.store(fd + 1, AtomicOrdering::Relaxed);
+
+        let path = PathBuf::from(format!("/proc/self/fd/{fd}"));
+        log::debug!("using in-memory file {path:?}");
+
+        Ok((path, memfd))
+    }
+}
+
+/// An error of a build that is not loaded again until the library changes, like a failed
+/// canary.
+#[derive(Debug)]
+struct Rejection(io::Error);
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Error for Rejection {}
+
+fn reject(e: io::Error) -> io::Error {
+    io::Error::other(Rejection(e))
+}
+
+fn is_rejection(e: &io::Error) -> bool {
+    e.get_ref().is_some_and(|e| e.is::<Rejection>())
+}
+
+/// Reads the whole library file, returning `None` if it was modified while being read.
+///
+/// The returned bytes are the exact snapshot that is hashed and loaded.
+fn read_snapshot(file: &mut File, metadata: &Metadata) -> io::Result<Option<Vec<u8>>> {
+    let mut bytes = Vec::with_capacity(metadata.len() as usize);
+    file.read_to_end(&mut bytes)?;
+
+    let metadata_after = file.metadata()?;
+
+    let is_unchanged = metadata_after.len() == bytes.len() as u64
+        && metadata_after.len() == metadata.len()
+        && metadata_after.modified()? == metadata.modified()?;
+
+    Ok(is_unchanged.then_some(bytes))
 }
 
 static WATCHER: OnceLock<Option<&Watcher>> = OnceLock::new();
@@ -0,0 +1,20 @@
+mod common;
+
+use common::{TestLib, build_test_lib, wait_for};
+
+#[test]
+fn revert_mismatched_archived_calls() {
+    let test_lib = TestLib::load(".tmp-archived", &[]);
+    let test_lib_version = test_lib.function("test_lib_version");
+    let test_lib_archived = test_lib.function("test_lib_archived");
+
+    assert_eq!(test_lib_archived(), 3);
+
+    build_test_lib("v2,mismatch");
+
+    wait_for(test_lib_version, 2);
+
+    // The archived input fails to validate against the v2 layout, v1 is called instead.
+    assert_eq!(test_lib_archived(), 3);
+    assert_eq!(test_lib_archived(), 3);
+}
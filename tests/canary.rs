@@ -0,0 +1,25 @@
+mod common;
+
+use common::{TestLib, assert_stays, build_test_lib, wait_for};
+
+#[test]
+fn reject_builds_failing_canaries() {
+    let test_lib = TestLib::load(".tmp-canary", &[]);
+    let test_lib_version = test_lib.function("test_lib_version");
+    let test_lib_canary_failed = test_lib.function("test_lib_canary_failed");
+
+    assert_eq!(test_lib_version(), 1);
+
+    // The canary of the v2 build fails, so it is rejected and v1 stays active.
+    build_test_lib("v2,reject");
+
+    wait_for(test_lib_canary_failed, 1);
+
+    // The rejected build is not loaded again by later polls.
+    assert_stays(test_lib_version, 1);
+    assert_eq!(test_lib_canary_failed(), 1);
+
+    build_test_lib("v2");
+
+    wait_for(test_lib_version, 2);
+}
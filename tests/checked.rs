@@ -0,0 +1,26 @@
+mod common;
+
+use common::{TestLib, build_test_lib, wait_for};
+
+#[test]
+fn revert_mismatched_checked_calls() {
+    let test_lib = TestLib::load(".tmp-checked", &[]);
+    let test_lib_version = test_lib.function("test_lib_version");
+    let test_lib_mismatch = test_lib.function("test_lib_mismatch");
+    let test_lib_incompatible_schema_changes =
+        test_lib.function("test_lib_incompatible_schema_changes");
+
+    assert_eq!(test_lib_mismatch(), 1);
+
+    build_test_lib("v2,mismatch");
+
+    wait_for(test_lib_version, 2);
+
+    // The v2 input of `test_lib_mismatch` was reported as incompatible when the build was
+    // loaded.
+    assert_eq!(test_lib_incompatible_schema_changes(), 1);
+
+    // The v2 implementation can not deserialize its input and is reverted to v1.
+    assert_eq!(test_lib_mismatch(), 1);
+    assert_eq!(test_lib_mismatch(), 1);
+}
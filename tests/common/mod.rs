@@ -0,0 +1,110 @@
+// Every test binary uses only some of the helpers.
+#![allow(dead_code)]
+
+use std::{
+    env, fs,
+    mem::ManuallyDrop,
+    path::PathBuf,
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use libloading::{Library, library_filename};
+
+/// The test library, loaded from a copy that later builds of the test library patch.
+///
+/// It is never unloaded, like the builds that patch it.
+pub struct TestLib(ManuallyDrop<Library>);
+
+impl TestLib {
+    /// Sets the environment variables `vars`, builds the "v1" test library, and loads it
+    /// from the directory `dir` of the profile.
+    pub fn load(dir: &str, vars: &[(&str, &str)]) -> Self {
+        for (name, value) in vars {
+            // SAFETY: every test binary runs a single test, and the test library reads its
+            // configuration on its first call, after this.
+            unsafe { env::set_var(name, value) };
+        }
+
+        build_test_lib("v1");
+
+        Self(ManuallyDrop::new(load_test_lib(dir)))
+    }
+
+    /// Returns the function `name` of the test library, which takes no arguments.
+    pub fn function(&self, name: &str) -> extern "C" fn() -> u32 {
+        // SAFETY: the test library exports such functions under these names.
+        unsafe { self.get(name) }
+    }
+
+    /// Returns the function `name` of the test library.
+    ///
+    /// # Safety
+    ///
+    /// `F` must be the type of the function.
+    pub unsafe fn get<F: Copy>(&self, name: &str) -> F {
+        unsafe { *self.0.get::<F>(name.as_bytes()).unwrap() }
+    }
+}
+
+/// The target directory of the current profile, which the test library is built into.
+pub fn profile_dir() -> PathBuf {
+    // Test executables are in the `deps` directory of the profile.
+    env::current_exe()
+        .unwrap()
+        .ancestors()
+        .nth(2)
+        .unwrap()
+        .to_path_buf()
+}
+
+pub fn build_test_lib(features: &str) {
+    let cargo_build_test_lib = Command::new(env!("CARGO"))
+        .current_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test-library"))
+        .args(["build", "-F", features, "--no-default-features"])
+        .spawn()
+        .unwrap()
+        .wait()
+        .unwrap()
+        .success();
+
+    assert!(cargo_build_test_lib, "failed to build test library");
+}
+
+/// Moves the test library out of the way of new builds, into the directory `dir` of the
+/// profile, and loads it.
+fn load_test_lib(dir: &str) -> Library {
+    let new_lib_dir = profile_dir().join(dir);
+    fs::create_dir_all(&new_lib_dir).unwrap();
+
+    let old_lib_path = profile_dir().join(library_filename("test_library"));
+    let new_lib_path = new_lib_dir.join(library_filename("test_library"));
+
+    fs::rename(&old_lib_path, &new_lib_path).unwrap();
+
+    unsafe { Library::new(new_lib_path).unwrap() }
+}
+
+/// Waits for `test_lib_fn` to return `value`, after the test library was rebuilt.
+pub fn wait_for(test_lib_fn: extern "C" fn() -> u32, value: u32) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    while test_lib_fn() != value {
+        assert!(
+            Instant::now() < deadline,
+            "test library did not return {value} after being patched"
+        );
+
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Polls for new builds for a while, asserting that `test_lib_fn` keeps returning `value`.
+pub fn assert_stays(test_lib_fn: extern "C" fn() -> u32, value: u32) {
+    let polling = Instant::now();
+
+    while polling.elapsed() < Duration::from_secs(1) {
+        assert_eq!(test_lib_fn(), value);
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
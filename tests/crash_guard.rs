@@ -0,0 +1,25 @@
+#![cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+
+mod common;
+
+use common::{TestLib, build_test_lib, wait_for};
+
+#[test]
+fn revert_crashing_patches() {
+    let test_lib = TestLib::load(".tmp-crash-guard", &[]);
+    let test_lib_version = test_lib.function("test_lib_version");
+    let test_lib_crash = test_lib.function("test_lib_crash");
+
+    assert_eq!(test_lib_crash(), 1);
+
+    build_test_lib("v2,crash");
+
+    wait_for(test_lib_version, 2);
+
+    // The v2 implementation crashes, the whole generation is reverted to v1.
+    assert_eq!(test_lib_crash(), 1);
+    assert_eq!(test_lib_version(), 1);
+}
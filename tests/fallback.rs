@@ -0,0 +1,20 @@
+mod common;
+
+use common::{TestLib, build_test_lib, wait_for};
+
+#[test]
+fn revert_panicking_patches() {
+    let test_lib = TestLib::load(".tmp-fallback", &[]);
+    let test_lib_version = test_lib.function("test_lib_version");
+    let test_lib_fallback = test_lib.function("test_lib_fallback");
+
+    assert_eq!(test_lib_fallback(), 1);
+
+    build_test_lib("v2,panic");
+
+    wait_for(test_lib_version, 2);
+
+    // The v2 implementation panics and is reverted to v1.
+    assert_eq!(test_lib_fallback(), 1);
+    assert_eq!(test_lib_fallback(), 1);
+}
@@ -0,0 +1,19 @@
+mod common;
+
+use common::{TestLib, build_test_lib, wait_for};
+
+#[test]
+fn dispatch_to_newest_build() {
+    let test_lib = TestLib::load(".tmp-hot-dyn", &[]);
+    let test_lib_version = test_lib.function("test_lib_version");
+    let test_lib_dyn = test_lib.function("test_lib_dyn");
+
+    assert_eq!(test_lib_dyn(), 1);
+
+    build_test_lib("v2");
+
+    wait_for(test_lib_version, 2);
+
+    // The trait object of v1 steps with the v2 vtable.
+    assert_eq!(test_lib_dyn(), 3);
+}
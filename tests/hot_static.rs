@@ -0,0 +1,19 @@
+mod common;
+
+use common::{TestLib, build_test_lib, wait_for};
+
+#[test]
+fn patch_statics() {
+    let test_lib = TestLib::load(".tmp-hot-static", &[]);
+    let test_lib_version = test_lib.function("test_lib_version");
+    let test_lib_tuned = test_lib.function("test_lib_tuned");
+
+    assert_eq!(test_lib_tuned(), 100);
+
+    build_test_lib("v2");
+
+    wait_for(test_lib_version, 2);
+
+    // The original build reads the value of the static in v2.
+    assert_eq!(test_lib_tuned(), 200);
+}
@@ -0,0 +1,47 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::fs;
+
+use common::{TestLib, build_test_lib, profile_dir, wait_for};
+
+#[test]
+fn patch_from_memfd() {
+    let test_lib = TestLib::load(".tmp-memfd-loader", &[("LIBHOTPATCH_LOADER", "memfd")]);
+    let test_lib_version = test_lib.function("test_lib_version");
+    let test_lib_persistent = test_lib.function("test_lib_persistent");
+
+    assert_eq!(test_lib_version(), 1);
+    assert_eq!(test_lib_persistent(), 1);
+
+    // Descriptors of loaded builds are closed, so v3 may reuse the descriptor number of v2.
+    for version in 2..=3 {
+        build_test_lib(&format!("v{version}"));
+
+        wait_for(test_lib_version, version);
+        assert_eq!(test_lib_persistent(), version);
+        assert_eq!(open_memfds(), 0);
+        assert_eq!(scratch_entries(), 0);
+    }
+}
+
+/// Counts the scratch directories and lock files of this process.
+fn scratch_entries() -> usize {
+    let pid = std::process::id();
+
+    fs::read_dir(profile_dir().join(".hotpatch"))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| name.starts_with(&format!("{pid}-")) || *name == format!("{pid}.lock"))
+        .count()
+}
+
+fn open_memfds() -> usize {
+    fs::read_dir("/proc/self/fd")
+        .unwrap()
+        .filter_map(|entry| fs::read_link(entry.ok()?.path()).ok())
+        .filter(|target| target.to_string_lossy().starts_with("/memfd:"))
+        .count()
+}
@@ -0,0 +1,24 @@
+mod common;
+
+use common::{TestLib, build_test_lib, wait_for};
+
+#[test]
+fn migrate_persistent_statics() {
+    let test_lib = TestLib::load(".tmp-migrate", &[]);
+    let test_lib_version = test_lib.function("test_lib_version");
+    let test_lib_migrated = test_lib.function("test_lib_migrated");
+    let test_lib_level = test_lib.function("test_lib_level");
+
+    assert_eq!(test_lib_migrated(), 10);
+    assert_eq!(test_lib_level(), 6);
+
+    build_test_lib("v2");
+
+    wait_for(test_lib_version, 2);
+
+    // The v2 implementation migrates the score of v1 to its own layout.
+    assert_eq!(test_lib_migrated(), 20);
+
+    // The type of the v2 level has the same size, and is still migrated from v1.
+    assert_eq!(test_lib_level(), 7);
+}
@@ -0,0 +1,26 @@
+mod common;
+
+use common::{TestLib, build_test_lib, wait_for};
+
+#[test]
+fn keep_persistent_statics() {
+    let test_lib = TestLib::load(".tmp-persistent", &[]);
+    let test_lib_version = test_lib.function("test_lib_version");
+    let test_lib_persistent = test_lib.function("test_lib_persistent");
+    let test_lib_gauge = test_lib.function("test_lib_gauge");
+
+    assert_eq!(test_lib_persistent(), 1);
+    assert_eq!(test_lib_gauge(), 6);
+    assert_eq!(test_lib_gauge(), 7);
+
+    build_test_lib("v2");
+
+    wait_for(test_lib_version, 2);
+
+    // The v2 implementation resolves the counter of v1.
+    assert_eq!(test_lib_persistent(), 2);
+
+    // The v2 gauge has a new version, so it starts over instead of reinterpreting the v1
+    // gauge.
+    assert_eq!(test_lib_gauge(), 6);
+}
@@ -1,4 +1,10 @@
-use libhotpatch::hotpatch;
+use std::rc::Rc;
+
+use libhotpatch::{
+    HotDyn,
+    codec::{HotpatchCodec, MessagePack},
+    hotpatch,
+};
 
 #[hotpatch]
 unsafe fn add(a: i32, b: i32) -> i32 {
@@ -21,6 +27,7 @@ unsafe fn add_tstruct(Tuple2(a, b): Tuple2<i32, i32>) -> i32 {
 }
 
 #[hotpatch]
+#[allow(clippy::needless_lifetimes)]
 unsafe fn lifetime_bound<'lt>(a: &'lt i32) -> &'lt i32 {
     a
 }
@@ -50,6 +57,56 @@ fn call_lifetime_bound() {
     assert_eq!(unsafe { lifetime_bound(&1) }, &1);
 }
 
+#[hotpatch]
+static SPEED: f32 = 4.0;
+
+#[hotpatch]
+static GREETING: &str = "hello";
+
+#[test]
+fn read_hot_statics() {
+    assert_eq!(SPEED.get(), 4.0);
+    assert_eq!(GREETING.get(), "hello");
+}
+
+trait Shape {
+    fn area(&self) -> f32;
+    fn scale(&mut self, factor: f32);
+}
+
+struct Square {
+    side: f32,
+    _owner: Rc<()>,
+}
+
+#[hotpatch]
+impl Shape for Square {
+    fn area(&self) -> f32 {
+        self.side * self.side
+    }
+
+    fn scale(&mut self, factor: f32) {
+        self.side *= factor;
+    }
+}
+
+#[test]
+fn call_hot_trait_objects() {
+    let owner = Rc::new(());
+
+    let mut square: HotDyn<dyn Shape> = HotDyn::new(Square {
+        side: 2.0,
+        _owner: owner.clone(),
+    });
+    square.scale(1.5);
+
+    assert_eq!(square.area(), 9.0);
+    assert_eq!(Rc::strong_count(&owner), 2);
+
+    drop(square);
+    assert_eq!(Rc::strong_count(&owner), 1);
+}
+
 #[hotpatch(checked)]
 unsafe fn add_checked(a: i32, b: i32) -> i32 {
     a + b
@@ -70,10 +127,244 @@ fn call_add_tuple_checked() {
     assert_eq!(unsafe { add_checked(2, 2) }, 4);
 }
 
+#[hotpatch(checked)]
+unsafe fn add_checked_refs(a: &i32, name: &str, rest: &[i32]) -> String {
+    format!("{name}: {}", a + rest.iter().sum::<i32>())
+}
+
+#[hotpatch(checked)]
+unsafe fn push_checked(values: &mut Vec<i32>, value: i32) -> usize {
+    values.push(value);
+    values.len()
+}
+
+#[hotpatch(checked, shadow)]
+unsafe fn push_checked_shadow(values: &mut Vec<i32>, value: &i32) -> usize {
+    values.push(*value);
+    values.len()
+}
+
+#[hotpatch(checked, fallback_on_panic)]
+unsafe fn push_checked_fallback(values: &mut Vec<i32>, value: i32) {
+    values.push(value);
+}
+
+#[test]
+fn call_add_checked_refs() {
+    assert_eq!(unsafe { add_checked_refs(&1, "sum", &[2, 3]) }, "sum: 6");
+}
+
+#[test]
+fn call_push_checked() {
+    let mut values = vec![1];
+
+    assert_eq!(unsafe { push_checked(&mut values, 2) }, 2);
+    assert_eq!(unsafe { push_checked_shadow(&mut values, &3) }, 3);
+    unsafe { push_checked_fallback(&mut values, 4) };
+
+    assert_eq!(values, [1, 2, 3, 4]);
+}
+
+#[hotpatch(checked = "messagepack")]
+unsafe fn add_checked_messagepack(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+#[cfg(feature = "postcard")]
+#[hotpatch(checked = "postcard")]
+unsafe fn add_checked_postcard(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+#[cfg(feature = "json")]
+#[hotpatch(checked = "json")]
+unsafe fn add_checked_json(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+struct DoubleEncoded;
+
+impl HotpatchCodec for DoubleEncoded {
+    fn serialize<T: serde::Serialize + ?Sized>(
+        value: &T,
+        writer: impl std::io::Write,
+    ) -> std::io::Result<()> {
+        let mut encoded = vec![];
+        MessagePack::serialize(value, &mut encoded)?;
+        MessagePack::serialize(&encoded, writer)
+    }
+
+    fn deserialize<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> std::io::Result<T> {
+        MessagePack::deserialize(&MessagePack::deserialize::<Vec<u8>>(bytes)?)
+    }
+}
+
+#[hotpatch(checked = "DoubleEncoded")]
+unsafe fn add_checked_custom_codec(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+#[test]
+fn call_add_checked_codecs() {
+    assert_eq!(unsafe { add_checked_messagepack(2, 2) }, 4);
+    assert_eq!(unsafe { add_checked_custom_codec(2, 2) }, 4);
+
+    #[cfg(feature = "postcard")]
+    assert_eq!(unsafe { add_checked_postcard(2, 2) }, 4);
+    #[cfg(feature = "json")]
+    assert_eq!(unsafe { add_checked_json(2, 2) }, 4);
+}
+
+#[cfg(feature = "archived")]
+#[derive(rkyv::Archive, rkyv::Serialize)]
+struct Points(Vec<[i32; 2]>);
+
+#[cfg(feature = "archived")]
+#[hotpatch(archived)]
+unsafe fn sum_archived(
+    points: libhotpatch::archived::View<'_, Points>,
+    scale: libhotpatch::archived::View<'_, i32>,
+) -> i32 {
+    let scale = scale.get().to_native();
+    points
+        .get()
+        .0
+        .iter()
+        .map(|[a, b]| (a.to_native() + b.to_native()) * scale)
+        .sum()
+}
+
+#[cfg(feature = "archived")]
+#[hotpatch(archived, shadow)]
+unsafe fn sum_archived_shadow(points: libhotpatch::archived::View<'_, Points>) -> i32 {
+    points
+        .get()
+        .0
+        .iter()
+        .map(|[a, b]| a.to_native() + b.to_native())
+        .sum()
+}
+
+#[cfg(feature = "archived")]
+#[hotpatch(archived, fallback_on_panic, on_mismatch = "default")]
+unsafe fn sum_archived_fallback(points: libhotpatch::archived::View<'_, Points>) -> i32 {
+    points
+        .get()
+        .0
+        .iter()
+        .map(|[a, b]| a.to_native() + b.to_native())
+        .sum()
+}
+
+#[cfg(feature = "archived")]
+#[test]
+fn call_sum_archived() {
+    use libhotpatch::archived::ArchivedBuf;
+
+    let points = ArchivedBuf::new(&Points(vec![[1, 1], [2, 2]])).unwrap();
+    let scale = ArchivedBuf::new(&2).unwrap();
+
+    assert_eq!(unsafe { sum_archived(points.view(), scale.view()) }, 12);
+    assert_eq!(unsafe { sum_archived_shadow(points.view()) }, 6);
+    assert_eq!(unsafe { sum_archived_fallback(points.view()) }, 6);
+}
+
+#[hotpatch(fallback_on_panic)]
+unsafe fn add_fallback(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+#[hotpatch(fallback_on_panic = "generation")]
+unsafe fn add_struct_fallback(Add { a, b }: Add) -> i32 {
+    a + b
+}
+
+#[hotpatch(checked, fallback_on_panic)]
+unsafe fn add_checked_fallback(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+#[hotpatch(fallback_on_panic)]
+unsafe fn panic_fallback() {
+    panic!("no previous generation to fall back to");
+}
+
+#[test]
+fn call_add_fallback() {
+    assert_eq!(unsafe { add_fallback(2, 2) }, 4);
+}
+
+#[test]
+fn call_add_struct_fallback() {
+    assert_eq!(unsafe { add_struct_fallback(Add { a: 2, b: 2 }) }, 4);
+}
+
+#[test]
+fn call_add_checked_fallback() {
+    assert_eq!(unsafe { add_checked_fallback(2, 2) }, 4);
+}
+
+#[test]
+#[should_panic = "no previous generation to fall back to"]
+fn call_panic_fallback() {
+    unsafe { panic_fallback() }
+}
+
+#[hotpatch(shadow)]
+unsafe fn add_shadow(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+#[hotpatch(shadow)]
+unsafe fn add_struct_shadow(Add { a, b }: Add) -> i32 {
+    a + b
+}
+
+#[hotpatch(checked, shadow)]
+unsafe fn add_checked_shadow(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+#[test]
+fn call_add_shadow() {
+    assert_eq!(unsafe { add_shadow(2, 2) }, 4);
+}
+
+#[test]
+fn call_add_struct_shadow() {
+    assert_eq!(unsafe { add_struct_shadow(Add { a: 2, b: 2 }) }, 4);
+}
+
+#[test]
+fn call_add_checked_shadow() {
+    assert_eq!(unsafe { add_checked_shadow(2, 2) }, 4);
+}
+
+#[hotpatch(checked, on_mismatch = "fallback")]
+unsafe fn add_checked_mismatch_fallback(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+#[hotpatch(checked, on_mismatch = "default", fallback_on_panic)]
+unsafe fn add_checked_mismatch_default(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+#[test]
+fn call_add_checked_mismatch_fallback() {
+    assert_eq!(unsafe { add_checked_mismatch_fallback(2, 2) }, 4);
+}
+
+#[test]
+fn call_add_checked_mismatch_default() {
+    assert_eq!(unsafe { add_checked_mismatch_default(2, 2) }, 4);
+}
+
 #[repr(C)]
 struct Tuple2<A, B>(A, B);
 
 #[repr(C)]
+#[derive(Clone)]
 struct Add {
     a: i32,
     b: i32,
@@ -0,0 +1,45 @@
+mod common;
+
+use std::fs;
+
+use common::{TestLib, profile_dir};
+
+#[test]
+fn bound_recordings() {
+    let recordings_dir = profile_dir().join(".hotpatch/recordings");
+    let _ = fs::remove_dir_all(&recordings_dir);
+    fs::create_dir_all(&recordings_dir).unwrap();
+
+    // A recording of a previous signature of `test_lib_replay`.
+    let superseded = recordings_dir.join(format!(
+        "test_library__replay__test_lib_replay_hotpatch-{:032x}.rec",
+        0
+    ));
+    fs::write(&superseded, b"").unwrap();
+
+    let test_lib = TestLib::load(
+        ".tmp-recording",
+        &[
+            ("LIBHOTPATCH_RECORD", "true"),
+            ("LIBHOTPATCH_RECORD_MAX_BYTES", "1000"),
+        ],
+    );
+    // SAFETY: `test_lib_replay` takes and returns a `u32`.
+    let test_lib_replay = unsafe { test_lib.get::<extern "C" fn(u32) -> u32>("test_lib_replay") };
+
+    for x in 0..1000 {
+        assert_eq!(test_lib_replay(x), x + 1);
+    }
+
+    assert!(!superseded.exists(), "superseded recording was not removed");
+
+    let recordings = fs::read_dir(&recordings_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().metadata().unwrap().len())
+        .collect::<Vec<_>>();
+
+    // The current recording and the one it was last rotated into, each up to half of the
+    // limit and one more call.
+    assert_eq!(recordings.len(), 2);
+    assert!(recordings.iter().all(|&len| len < 600), "{recordings:?}");
+}
@@ -0,0 +1,33 @@
+mod common;
+
+use std::fs;
+
+use common::{TestLib, build_test_lib, profile_dir, wait_for};
+
+#[test]
+fn replay_recorded_calls() {
+    let _ = fs::remove_dir_all(profile_dir().join(".hotpatch/recordings"));
+
+    let test_lib = TestLib::load(
+        ".tmp-replay",
+        &[
+            ("LIBHOTPATCH_RECORD", "true"),
+            ("LIBHOTPATCH_REPLAY", "warn"),
+        ],
+    );
+    let test_lib_version = test_lib.function("test_lib_version");
+    let test_lib_replay_divergences = test_lib.function("test_lib_replay_divergences");
+    // SAFETY: `test_lib_replay` takes and returns a `u32`.
+    let test_lib_replay = unsafe { test_lib.get::<extern "C" fn(u32) -> u32>("test_lib_replay") };
+
+    // Recorded, and replayed against v2.
+    assert_eq!(test_lib_replay(1), 2);
+
+    build_test_lib("v2");
+
+    wait_for(test_lib_version, 2);
+
+    // The v2 output diverges from the recorded v1 output.
+    assert_eq!(test_lib_replay_divergences(), 1);
+    assert_eq!(test_lib_replay(1), 3);
+}
@@ -0,0 +1,26 @@
+mod common;
+
+use std::fs;
+
+use common::{TestLib, assert_stays, build_test_lib, profile_dir, wait_for};
+
+#[test]
+fn retry_builds_that_failed_to_load() {
+    let test_lib = TestLib::load(".tmp-retry", &[]);
+    let test_lib_version = test_lib.function("test_lib_version");
+
+    assert_eq!(test_lib_version(), 1);
+
+    // The lock file can not be created while a directory is in its place.
+    let lock_path = profile_dir().join(format!(".hotpatch/{}.lock", std::process::id()));
+    fs::create_dir(&lock_path).unwrap();
+
+    build_test_lib("v2");
+
+    assert_stays(test_lib_version, 1);
+
+    // The same build is loaded once the lock file can be created.
+    fs::remove_dir(&lock_path).unwrap();
+
+    wait_for(test_lib_version, 2);
+}
@@ -0,0 +1,25 @@
+mod common;
+
+use common::{TestLib, build_test_lib, wait_for};
+
+#[test]
+fn report_schema_changes() {
+    let test_lib = TestLib::load(".tmp-schema-changes", &[]);
+    let test_lib_version = test_lib.function("test_lib_version");
+    let test_lib_settings = test_lib.function("test_lib_settings");
+    let test_lib_compatible_schema_changes =
+        test_lib.function("test_lib_compatible_schema_changes");
+    let test_lib_incompatible_schema_changes =
+        test_lib.function("test_lib_incompatible_schema_changes");
+
+    assert_eq!(test_lib_settings(), 2);
+
+    build_test_lib("v2");
+
+    wait_for(test_lib_version, 2);
+
+    // The field added to the v2 input was reported as compatible.
+    assert_eq!(test_lib_compatible_schema_changes(), 1);
+    assert_eq!(test_lib_incompatible_schema_changes(), 0);
+    assert_eq!(test_lib_settings(), 3);
+}
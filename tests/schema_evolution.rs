@@ -0,0 +1,46 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{TestLib, assert_stays, build_test_lib, wait_for};
+
+#[test]
+fn reject_incompatible_schema_changes() {
+    let test_lib = TestLib::load(
+        ".tmp-schema-evolution",
+        &[("LIBHOTPATCH_SCHEMA_EVOLUTION", "tolerant")],
+    );
+    let test_lib_version = test_lib.function("test_lib_version");
+    let test_lib_mismatch = test_lib.function("test_lib_mismatch");
+    let test_lib_compatible_schema_changes =
+        test_lib.function("test_lib_compatible_schema_changes");
+    let test_lib_incompatible_schema_changes =
+        test_lib.function("test_lib_incompatible_schema_changes");
+
+    assert_eq!(test_lib_version(), 1);
+
+    // The field added to the v2 input is compatible, so v2 is patched in.
+    build_test_lib("v2");
+
+    wait_for(test_lib_version, 2);
+    assert_eq!(test_lib_compatible_schema_changes(), 1);
+    assert_eq!(test_lib_incompatible_schema_changes(), 0);
+
+    // The v3 input of `test_lib_mismatch` can not be deserialized from the v2 input.
+    build_test_lib("v3,mismatch");
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    while test_lib_incompatible_schema_changes() == 0 {
+        assert!(Instant::now() < deadline, "the v3 build was not inspected");
+
+        // Polls for new builds.
+        assert_eq!(test_lib_version(), 2);
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    // The whole v3 build is rejected, and v2 stays active.
+    assert_stays(test_lib_version, 2);
+    assert_eq!(test_lib_mismatch(), 2);
+    assert_eq!(test_lib_incompatible_schema_changes(), 1);
+}
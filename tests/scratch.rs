@@ -0,0 +1,43 @@
+mod common;
+
+use std::{fs, path::PathBuf, process::Command};
+
+use common::{TestLib, profile_dir};
+use libloading::library_filename;
+
+#[test]
+fn collect_stale_scratch_files() {
+    let (stale_dir, stale_lock) = create_stale_scratch_files();
+
+    let test_lib = TestLib::load(".tmp-scratch", &[]);
+    let test_lib_version = test_lib.function("test_lib_version");
+
+    assert_eq!(test_lib_version(), 1);
+
+    assert!(
+        !stale_dir.exists(),
+        "stale scratch directory was not removed"
+    );
+    assert!(!stale_lock.exists(), "stale lock file was not removed");
+}
+
+fn create_stale_scratch_files() -> (PathBuf, PathBuf) {
+    // The pid of a process that has already exited.
+    let mut child = Command::new(env!("CARGO"))
+        .arg("--version")
+        .spawn()
+        .unwrap();
+    let dead_pid = child.id();
+    child.wait().unwrap();
+
+    let scratch_dir = profile_dir().join(".hotpatch");
+
+    let stale_dir = scratch_dir.join(format!("{dead_pid}-stale"));
+    fs::create_dir_all(&stale_dir).unwrap();
+    fs::write(stale_dir.join(library_filename("test_library")), b"").unwrap();
+
+    let stale_lock = scratch_dir.join(format!("{dead_pid}.lock"));
+    fs::write(&stale_lock, b"").unwrap();
+
+    (stale_dir, stale_lock)
+}
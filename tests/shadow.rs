@@ -0,0 +1,22 @@
+mod common;
+
+use common::{TestLib, build_test_lib, wait_for};
+
+#[test]
+fn report_shadow_divergences() {
+    let test_lib = TestLib::load(".tmp-shadow", &[]);
+    let test_lib_version = test_lib.function("test_lib_version");
+    let test_lib_shadow = test_lib.function("test_lib_shadow");
+    let test_lib_shadow_divergences = test_lib.function("test_lib_shadow_divergences");
+
+    assert_eq!(test_lib_shadow(), 1);
+    assert_eq!(test_lib_shadow_divergences(), 0);
+
+    build_test_lib("v2");
+
+    wait_for(test_lib_version, 2);
+
+    // The v2 output is returned, and its divergence from v1 is reported.
+    assert_eq!(test_lib_shadow(), 2);
+    assert_eq!(test_lib_shadow_divergences(), 1);
+}
@@ -0,0 +1,19 @@
+mod common;
+
+use common::{TestLib, build_test_lib, wait_for};
+
+#[test]
+fn initialize_once_across_builds() {
+    let test_lib = TestLib::load(".tmp-sync", &[]);
+    let test_lib_version = test_lib.function("test_lib_version");
+    let test_lib_initialized = test_lib.function("test_lib_initialized");
+
+    assert_eq!(test_lib_initialized(), 11);
+
+    build_test_lib("v2");
+
+    wait_for(test_lib_version, 2);
+
+    // The v2 implementation does not initialize again.
+    assert_eq!(test_lib_initialized(), 11);
+}
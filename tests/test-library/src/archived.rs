@@ -0,0 +1,29 @@
+//! `#[hotpatch(archived)]` functions, whose archived input changes layout in builds with the
+//! "mismatch" feature.
+
+use libhotpatch::archived::{ArchivedBuf, View};
+
+use crate::version;
+
+#[derive(rkyv::Archive, rkyv::Serialize)]
+struct Mesh {
+    vertices: Vec<[f32; 3]>,
+    #[cfg(feature = "mismatch")]
+    indices: Vec<u32>,
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn test_lib_archived() -> u32 {
+    let mesh = Mesh {
+        vertices: vec![[0.0; 3]; 2],
+        #[cfg(feature = "mismatch")]
+        indices: vec![],
+    };
+    let mesh = ArchivedBuf::new(&mesh).unwrap();
+    unsafe { test_lib_archived_hotpatch(mesh.view()) }
+}
+
+#[libhotpatch::hotpatch(archived, on_mismatch = "fallback")]
+unsafe fn test_lib_archived_hotpatch(mesh: View<'_, Mesh>) -> u32 {
+    mesh.get().vertices.len() as u32 + version()
+}
@@ -0,0 +1,25 @@
+//! `#[canary]` checks, which fail in builds with the "reject" feature.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::version;
+
+pub static FAILURES: AtomicU32 = AtomicU32::new(0);
+
+#[unsafe(no_mangle)]
+extern "C" fn test_lib_canary_failed() -> u32 {
+    // Polls for new builds.
+    let _ = unsafe { test_lib_guarded_hotpatch() };
+    FAILURES.load(Ordering::Relaxed)
+}
+
+#[libhotpatch::hotpatch]
+unsafe fn test_lib_guarded_hotpatch() -> u32 {
+    version()
+}
+
+#[libhotpatch::canary(test_lib_guarded_hotpatch)]
+fn test_lib_guarded_canary() -> bool {
+    let version = unsafe { test_lib_guarded_hotpatch() };
+    cfg!(not(feature = "reject")) && version != 0
+}
@@ -0,0 +1,59 @@
+//! `#[hotpatch(checked)]` functions, whose inputs change compatibly in every build after v1,
+//! and incompatibly in builds with the "mismatch" feature.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::version;
+
+pub static COMPATIBLE_SCHEMA_CHANGES: AtomicU32 = AtomicU32::new(0);
+pub static INCOMPATIBLE_SCHEMA_CHANGES: AtomicU32 = AtomicU32::new(0);
+
+#[unsafe(no_mangle)]
+extern "C" fn test_lib_compatible_schema_changes() -> u32 {
+    COMPATIBLE_SCHEMA_CHANGES.load(Ordering::Relaxed)
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn test_lib_incompatible_schema_changes() -> u32 {
+    INCOMPATIBLE_SCHEMA_CHANGES.load(Ordering::Relaxed)
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn test_lib_mismatch() -> u32 {
+    unsafe { test_lib_mismatch_hotpatch(Default::default()) }
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn test_lib_settings() -> u32 {
+    let settings = Settings {
+        volume: 1,
+        #[cfg(not(feature = "v1"))]
+        muted: false,
+    };
+    unsafe { test_lib_settings_hotpatch(settings) }
+}
+
+// Builds with the "mismatch" feature can not deserialize the input of other builds.
+#[cfg(not(feature = "mismatch"))]
+type MismatchInput = u32;
+#[cfg(feature = "mismatch")]
+type MismatchInput = String;
+
+#[libhotpatch::hotpatch(checked, on_mismatch = "fallback")]
+unsafe fn test_lib_mismatch_hotpatch(_input: MismatchInput) -> u32 {
+    version()
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Settings {
+    volume: u32,
+    // Builds after v1 add a field, which is compatible since it has a default.
+    #[cfg(not(feature = "v1"))]
+    #[serde(default)]
+    muted: bool,
+}
+
+#[libhotpatch::hotpatch(checked)]
+unsafe fn test_lib_settings_hotpatch(settings: Settings) -> u32 {
+    settings.volume + version()
+}
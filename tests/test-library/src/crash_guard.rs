@@ -0,0 +1,17 @@
+//! `#[hotpatch(crash_guard)]` functions, which crash in builds with the "crash" feature.
+
+use crate::version;
+
+#[unsafe(no_mangle)]
+extern "C" fn test_lib_crash() -> u32 {
+    unsafe { test_lib_crash_hotpatch() }
+}
+
+#[libhotpatch::hotpatch(crash_guard)]
+unsafe fn test_lib_crash_hotpatch() -> u32 {
+    #[cfg(feature = "crash")]
+    unsafe {
+        std::ptr::write_volatile(std::ptr::null_mut::<u32>(), version());
+    }
+    version()
+}
@@ -0,0 +1,17 @@
+//! `#[hotpatch(fallback_on_panic)]` functions, which panic in builds with the "panic"
+//! feature.
+
+use crate::version;
+
+#[unsafe(no_mangle)]
+extern "C" fn test_lib_fallback() -> u32 {
+    unsafe { test_lib_fallback_hotpatch() }
+}
+
+#[libhotpatch::hotpatch(fallback_on_panic)]
+unsafe fn test_lib_fallback_hotpatch() -> u32 {
+    #[cfg(feature = "panic")]
+    panic!("v{} panicked", version());
+    #[cfg(not(feature = "panic"))]
+    version()
+}
@@ -0,0 +1,34 @@
+//! `HotDyn` trait objects.
+
+use std::sync::Mutex;
+
+use libhotpatch::HotDyn;
+
+use crate::version;
+
+trait Counter: Send {
+    fn step(&mut self) -> u32;
+}
+
+struct Steps {
+    count: u32,
+}
+
+// Steps by the version of the newest build.
+#[libhotpatch::hotpatch]
+impl Counter for Steps {
+    fn step(&mut self) -> u32 {
+        self.count += version();
+        self.count
+    }
+}
+
+static COUNTER: Mutex<Option<HotDyn<dyn Counter>>> = Mutex::new(None);
+
+#[unsafe(no_mangle)]
+extern "C" fn test_lib_dyn() -> u32 {
+    let mut counter = COUNTER.lock().unwrap();
+    counter
+        .get_or_insert_with(|| HotDyn::new(Steps { count: 0 }))
+        .step()
+}
@@ -0,0 +1,12 @@
+//! `#[hotpatch]` statics.
+
+use crate::version;
+
+#[unsafe(no_mangle)]
+extern "C" fn test_lib_tuned() -> u32 {
+    TUNED.get()
+}
+
+// Read by the original build, which is not a `#[hotpatch]` function.
+#[libhotpatch::hotpatch]
+static TUNED: u32 = version() * 100;
@@ -1,20 +1,71 @@
-use std::sync::Once;
+//! The library that the integration tests load and patch, with one module per feature.
+//!
+//! Every test builds it once with the "v1" feature, loads that build, and then builds it
+//! again with later versions and the behaviour features of the module it tests.
+
+use std::sync::atomic::Ordering;
+
+use libhotpatch::{HotpatchEvent, sync::HotOnce};
+
+mod archived;
+mod canary;
+mod checked;
+mod crash_guard;
+mod fallback;
+mod hot_dyn;
+mod hot_static;
+mod load_failure;
+mod migrate;
+mod persistent;
+mod replay;
+mod shadow;
+mod sync;
+mod thread_local;
 
 #[unsafe(no_mangle)]
 extern "C" fn test_lib_version() -> u32 {
-    static ONCE: Once = Once::new();
+    static ONCE: HotOnce = HotOnce::new("test_library::ONCE");
     ONCE.call_once(|| {
         env_logger::init();
+        libhotpatch::set_event_handler(|event| match event {
+            HotpatchEvent::ShadowDivergence { .. } => {
+                shadow::DIVERGENCES.fetch_add(1, Ordering::Relaxed);
+            }
+            HotpatchEvent::ReplayDivergence { .. } => {
+                replay::DIVERGENCES.fetch_add(1, Ordering::Relaxed);
+            }
+            HotpatchEvent::CanaryFailed { .. } => {
+                canary::FAILURES.fetch_add(1, Ordering::Relaxed);
+            }
+            HotpatchEvent::SchemaChanged { compatible, .. } => {
+                let changes = if *compatible {
+                    &checked::COMPATIBLE_SCHEMA_CHANGES
+                } else {
+                    &checked::INCOMPATIBLE_SCHEMA_CHANGES
+                };
+                changes.fetch_add(1, Ordering::Relaxed);
+            }
+            _ => {}
+        });
     });
     unsafe { test_lib_version_hotpatch() }
 }
 
 #[libhotpatch::hotpatch]
 unsafe fn test_lib_version_hotpatch() -> u32 {
-    #[cfg(feature = "v1")]
-    return 1;
-    #[cfg(feature = "v2")]
-    return 2;
-    #[cfg(feature = "v3")]
-    return 3;
+    version()
+}
+
+const fn version() -> u32 {
+    if cfg!(feature = "v5") {
+        5
+    } else if cfg!(feature = "v4") {
+        4
+    } else if cfg!(feature = "v3") {
+        3
+    } else if cfg!(feature = "v2") {
+        2
+    } else {
+        1
+    }
 }
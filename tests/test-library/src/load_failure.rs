@@ -0,0 +1,38 @@
+//! Builds that fail to load, with the "abort" or "unresolved" feature.
+
+use crate::version;
+
+#[unsafe(no_mangle)]
+extern "C" fn test_lib_resolved() -> u32 {
+    unsafe { test_lib_resolved_hotpatch() }
+}
+
+// Builds with the "unresolved" feature reference a symbol that no library defines.
+#[libhotpatch::hotpatch]
+unsafe fn test_lib_resolved_hotpatch() -> u32 {
+    #[cfg(feature = "unresolved")]
+    unsafe {
+        test_lib_unresolved();
+    }
+    version()
+}
+
+#[cfg(feature = "unresolved")]
+unsafe extern "C" {
+    fn test_lib_unresolved();
+}
+
+// Builds with the "abort" feature abort when they are loaded, after creating the file at
+// `TEST_LIB_ABORT_MARKER`.
+#[cfg(feature = "abort")]
+#[used]
+#[unsafe(link_section = ".init_array")]
+static TEST_LIB_ABORT: extern "C" fn() = {
+    extern "C" fn abort() {
+        if let Some(marker) = std::env::var_os("TEST_LIB_ABORT_MARKER") {
+            let _ = std::fs::write(marker, b"");
+        }
+        std::process::abort();
+    }
+    abort
+};
@@ -0,0 +1,60 @@
+//! Persistent statics migrated with `serde`, whose type changes in every build after v1.
+
+use std::sync::Mutex;
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct Score {
+    points: u32,
+    // Builds after v1 add a field, so the score is migrated to its new layout.
+    #[cfg(not(feature = "v1"))]
+    #[serde(default)]
+    bonus: u32,
+}
+
+libhotpatch::persistent! {
+    #[migrate(serde)]
+    static SCORE: Mutex<Score> = Mutex::new(Score::default());
+}
+
+// Builds after v1 change the type of the field without changing the layout of the level,
+// so it is migrated rather than reinterpreted.
+#[cfg(feature = "v1")]
+type LevelValue = u32;
+#[cfg(not(feature = "v1"))]
+type LevelValue = f32;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Level {
+    value: LevelValue,
+}
+
+libhotpatch::persistent! {
+    #[migrate(serde)]
+    static LEVEL: Mutex<Level> = Mutex::new(Level { value: LevelValue::from(5u8) });
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn test_lib_migrated() -> u32 {
+    unsafe { test_lib_migrated_hotpatch() }
+}
+
+// Adds 10 points to a score kept across generations.
+#[libhotpatch::hotpatch]
+unsafe fn test_lib_migrated_hotpatch() -> u32 {
+    let mut score = SCORE.lock().unwrap();
+    score.points += 10;
+    score.points
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn test_lib_level() -> u32 {
+    unsafe { test_lib_level_hotpatch() }
+}
+
+// Increments a level that starts at 5.
+#[libhotpatch::hotpatch]
+unsafe fn test_lib_level_hotpatch() -> u32 {
+    let mut level = LEVEL.lock().unwrap();
+    level.value += LevelValue::from(1u8);
+    f64::from(level.value) as u32
+}
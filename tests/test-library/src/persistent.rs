@@ -0,0 +1,53 @@
+//! Persistent statics.
+
+use std::sync::{
+    Mutex,
+    atomic::{AtomicU32, Ordering},
+};
+
+libhotpatch::persistent! {
+    #[version(1)]
+    static PERSISTENT_CALLS: AtomicU32 = AtomicU32::new(0);
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn test_lib_persistent() -> u32 {
+    unsafe { test_lib_persistent_hotpatch() }
+}
+
+// Counts calls across generations.
+#[libhotpatch::hotpatch]
+unsafe fn test_lib_persistent_hotpatch() -> u32 {
+    PERSISTENT_CALLS.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+// Builds after v1 change the type of the field without changing the layout of the gauge,
+// and its version along with it.
+#[cfg(feature = "v1")]
+type GaugeValue = u32;
+#[cfg(not(feature = "v1"))]
+type GaugeValue = f32;
+
+const GAUGE_VERSION: u32 = if cfg!(feature = "v1") { 1 } else { 2 };
+
+struct Gauge {
+    value: GaugeValue,
+}
+
+libhotpatch::persistent! {
+    #[version(GAUGE_VERSION)]
+    static GAUGE: Mutex<Gauge> = Mutex::new(Gauge { value: GaugeValue::from(5u8) });
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn test_lib_gauge() -> u32 {
+    unsafe { test_lib_gauge_hotpatch() }
+}
+
+// Increments a gauge that starts at 5 in every version.
+#[libhotpatch::hotpatch]
+unsafe fn test_lib_gauge_hotpatch() -> u32 {
+    let mut gauge = GAUGE.lock().unwrap();
+    gauge.value += GaugeValue::from(1u8);
+    f64::from(gauge.value) as u32
+}
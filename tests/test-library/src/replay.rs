@@ -0,0 +1,22 @@
+//! Recorded `#[hotpatch(checked)]` functions, whose output changes in every build.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::version;
+
+pub static DIVERGENCES: AtomicU32 = AtomicU32::new(0);
+
+#[unsafe(no_mangle)]
+extern "C" fn test_lib_replay(x: u32) -> u32 {
+    unsafe { test_lib_replay_hotpatch(x) }
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn test_lib_replay_divergences() -> u32 {
+    DIVERGENCES.load(Ordering::Relaxed)
+}
+
+#[libhotpatch::hotpatch(checked)]
+unsafe fn test_lib_replay_hotpatch(x: u32) -> u32 {
+    x + version()
+}
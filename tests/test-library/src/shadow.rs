@@ -0,0 +1,22 @@
+//! `#[hotpatch(shadow)]` functions, whose output changes in every build.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::version;
+
+pub static DIVERGENCES: AtomicU32 = AtomicU32::new(0);
+
+#[unsafe(no_mangle)]
+extern "C" fn test_lib_shadow() -> u32 {
+    unsafe { test_lib_shadow_hotpatch() }
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn test_lib_shadow_divergences() -> u32 {
+    DIVERGENCES.load(Ordering::Relaxed)
+}
+
+#[libhotpatch::hotpatch(shadow)]
+unsafe fn test_lib_shadow_hotpatch() -> u32 {
+    version()
+}
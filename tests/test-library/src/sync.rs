@@ -0,0 +1,30 @@
+//! `HotOnce` and `HotLazy`.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use libhotpatch::sync::{HotLazy, HotOnce};
+
+use crate::version;
+
+libhotpatch::persistent! {
+    #[version(1)]
+    static INITIALIZATIONS: AtomicU32 = AtomicU32::new(0);
+}
+
+static INITIALIZED: HotOnce = HotOnce::new("test_library::INITIALIZED");
+static FIRST_VERSION: HotLazy<u32> = HotLazy::new("test_library::FIRST_VERSION", version);
+
+#[unsafe(no_mangle)]
+extern "C" fn test_lib_initialized() -> u32 {
+    unsafe { test_lib_initialized_hotpatch() }
+}
+
+// Initializes once across generations, and returns the number of initializations (tens) and
+// the version that initialized the lazy value (units).
+#[libhotpatch::hotpatch]
+unsafe fn test_lib_initialized_hotpatch() -> u32 {
+    INITIALIZED.call_once(|| {
+        INITIALIZATIONS.fetch_add(1, Ordering::Relaxed);
+    });
+    INITIALIZATIONS.load(Ordering::Relaxed) * 10 + *FIRST_VERSION
+}
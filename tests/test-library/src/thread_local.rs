@@ -0,0 +1,21 @@
+//! Thread-locals declared with `hot_thread_local!`.
+
+use std::cell::Cell;
+
+libhotpatch::hot_thread_local! {
+    static THREAD_CALLS: Cell<u32> = Cell::new(0);
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn test_lib_thread_local() -> u32 {
+    unsafe { test_lib_thread_local_hotpatch() }
+}
+
+// Counts calls of the current thread across generations.
+#[libhotpatch::hotpatch]
+unsafe fn test_lib_thread_local_hotpatch() -> u32 {
+    THREAD_CALLS.with(|calls| {
+        calls.set(calls.get() + 1);
+        calls.get()
+    })
+}
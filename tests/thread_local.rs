@@ -0,0 +1,23 @@
+mod common;
+
+use common::{TestLib, build_test_lib, wait_for};
+
+#[test]
+fn keep_thread_locals() {
+    let test_lib = TestLib::load(".tmp-thread-local", &[]);
+    let test_lib_version = test_lib.function("test_lib_version");
+    let test_lib_thread_local = test_lib.function("test_lib_thread_local");
+
+    let spawn_thread_local = move || std::thread::spawn(move || test_lib_thread_local());
+
+    assert_eq!(test_lib_thread_local(), 1);
+    assert_eq!(spawn_thread_local().join().unwrap(), 1);
+
+    build_test_lib("v2");
+
+    wait_for(test_lib_version, 2);
+
+    // The v2 implementation resolves the thread-local of v1 on this thread only.
+    assert_eq!(test_lib_thread_local(), 2);
+    assert_eq!(spawn_thread_local().join().unwrap(), 1);
+}
@@ -0,0 +1,50 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+
+use common::{TestLib, assert_stays, build_test_lib, profile_dir, wait_for};
+
+#[test]
+fn reject_builds_that_fail_to_load() {
+    let abort_marker = profile_dir().join(".tmp-validate-in-child/aborted");
+    let _ = fs::remove_file(&abort_marker);
+
+    let test_lib = TestLib::load(
+        ".tmp-validate-in-child",
+        &[
+            ("LIBHOTPATCH_VALIDATE_IN_CHILD", "1"),
+            ("TEST_LIB_ABORT_MARKER", abort_marker.to_str().unwrap()),
+        ],
+    );
+    let test_lib_version = test_lib.function("test_lib_version");
+
+    assert_eq!(test_lib_version(), 1);
+
+    // The constructor of the v2 build aborts the child process that validates it.
+    build_test_lib("v2,abort");
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    while !abort_marker.exists() {
+        assert!(Instant::now() < deadline, "the v2 build was not validated");
+
+        assert_eq!(test_lib_version(), 1);
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    assert_stays(test_lib_version, 1);
+
+    // The v2 build references a symbol that can not be resolved, and would crash if called.
+    build_test_lib("v2,unresolved");
+
+    assert_stays(test_lib_version, 1);
+
+    build_test_lib("v2");
+
+    wait_for(test_lib_version, 2);
+}